@@ -0,0 +1,285 @@
+use crate::exec::{exists, repo_env, repo_exec, ExecOptions};
+use crate::git::{Git, GitError};
+use crate::repos::VaqRepo;
+use crate::storage::Storage;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use derive_more::Display;
+use log::info;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The change rolled out by a batch: either a patch file applied to the working tree,
+/// or a command run in each repo through the same machinery as `vaquera exec`.
+pub enum BatchChange {
+	Patch(Vec<u8>),
+	Script(Vec<String>),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Display, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BatchRepoStatus {
+	#[display("changed")]
+	Changed,
+
+	#[display("no-op")]
+	NoOp,
+
+	#[display("failed")]
+	Failed,
+
+	#[display("pushed")]
+	Pushed,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchRepoState {
+	pub status: BatchRepoStatus,
+
+	/// Branch the repo was on before the batch branch was created, restored by `abandon`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub base_branch: Option<String>,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+/// Persisted in `.vaquera-batch.toml` so a batch can be followed up over several days.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchState {
+	pub branch: String,
+	pub message: String,
+	pub repos: BTreeMap<String, BatchRepoState>,
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum BatchError {
+	#[error("A batch is already in progress on branch `{0}`, push or abandon it first")]
+	AlreadyInProgress(String),
+
+	#[error("No batch in progress")]
+	NotInProgress,
+
+	#[error("Failed to parse batch state as valid TOML. {0}")]
+	Parse(toml::de::Error),
+
+	#[error("Failed to generate toml for batch state. {0}")]
+	Serialize(toml::ser::Error),
+
+	#[error("Failed to write batch state. {0}")]
+	Save(std::io::Error),
+}
+
+pub struct VaqBatch {
+	storage: Box<dyn Storage>,
+	git: Box<dyn Git>,
+}
+
+impl VaqBatch {
+	pub fn new(storage: Box<dyn Storage>, git: Box<dyn Git>) -> Self {
+		Self { storage, git }
+	}
+
+	/// Creates `branch` in each repo, applies the change and commits it with `message`.
+	/// Repos where the change produced no diff are switched back to their original branch.
+	pub fn apply(
+		&self,
+		branch: &str,
+		message: &str,
+		change: &BatchChange,
+		repos: Vec<VaqRepo>,
+	) -> Result<BatchState, BatchError> {
+		if let Some(state) = self.load()? {
+			return Err(BatchError::AlreadyInProgress(state.branch));
+		}
+
+		let mut state = BatchState {
+			branch: branch.to_string(),
+			message: message.to_string(),
+			repos: BTreeMap::new(),
+		};
+
+		for repo in repos {
+			let repo_state = self.apply_repo(&repo, branch, message, change);
+			info!("{}: {}", repo.path.display(), repo_state.status);
+
+			state.repos.insert(repo.path.display().to_string(), repo_state);
+		}
+
+		// Without a branch left anywhere there is nothing to push or abandon, so don't keep the
+		// batch around to block the next one
+		if state.repos.values().any(|repo_state| repo_state.base_branch.is_some()) {
+			self.save(&state)?;
+		} else {
+			info!("No repo changed, batch not recorded");
+		}
+
+		Ok(state)
+	}
+
+	pub fn status(&self) -> Result<BatchState, BatchError> {
+		self.load()?.ok_or(BatchError::NotInProgress)
+	}
+
+	/// Pushes the batch branch of every changed repo. Returns the number of failed pushes.
+	pub fn push(&self, remote_name: &str) -> Result<usize, BatchError> {
+		let mut state = self.status()?;
+		let refspec = format!("refs/heads/{0}:refs/heads/{0}", state.branch);
+		let mut error_count = 0;
+
+		for (path, repo_state) in state.repos.iter_mut() {
+			if repo_state.status != BatchRepoStatus::Changed {
+				continue;
+			}
+
+			match self.git.push(Path::new(path), remote_name, refspec.as_str()) {
+				Ok(()) => {
+					repo_state.status = BatchRepoStatus::Pushed;
+					repo_state.error = None;
+					info!("Pushed {} to {}", path, remote_name);
+				}
+
+				Err(error) => {
+					eprintln!("Warning: Could not push {}: {}", path, error);
+					repo_state.error = Some(error.to_string());
+					error_count += 1;
+				}
+			}
+		}
+
+		self.save(&state)?;
+		Ok(error_count)
+	}
+
+	/// Switches every repo back to its original branch, deletes the local batch branch
+	/// and forgets the batch. Branches already pushed are left on the remote.
+	pub fn abandon(&self) -> Result<(), BatchError> {
+		let state = self.status()?;
+
+		for (path, repo_state) in &state.repos {
+			let Some(base_branch) = &repo_state.base_branch else {
+				continue;
+			};
+
+			let path = Path::new(path);
+			let restored = self.git.checkout_branch(path, base_branch)
+				.and_then(|_| self.git.delete_branch(path, state.branch.as_str()));
+
+			match restored {
+				Ok(()) => info!("Restored {} to {}", path.display(), base_branch),
+				Err(error) => eprintln!("Warning: Could not restore {}: {}", path.display(), error),
+			}
+
+			if repo_state.status == BatchRepoStatus::Pushed {
+				eprintln!("Note: {} was already pushed, remote branch left in place", path.display());
+			}
+		}
+
+		self.storage.remove();
+		Ok(())
+	}
+
+	/// Applies the change to one repo on a new `branch`. Repos with uncommitted work, which
+	/// the batch commit would take along, or with a `branch` of their own are left alone.
+	/// `base_branch` is only recorded while the batch branch exists, so `abandon` never
+	/// deletes a branch the batch didn't create.
+	fn apply_repo(&self, repo: &VaqRepo, branch: &str, message: &str, change: &BatchChange) -> BatchRepoState {
+		let path = repo.path.as_path();
+		let failed = |base_branch: Option<String>, error: String| BatchRepoState {
+			status: BatchRepoStatus::Failed,
+			base_branch,
+			error: Some(error),
+		};
+
+		if !exists(path) {
+			return failed(None, "Repo folder missing".to_string());
+		}
+
+		let base_branch = match self.git.current_branch(path) {
+			Ok(name) => name,
+			Err(error) => return failed(None, error.to_string()),
+		};
+
+		match self.git.status(path) {
+			Ok(status) if status.dirty => return failed(None, "Uncommitted changes, commit or stash them first".to_string()),
+			Ok(_) => {}
+			Err(error) => return failed(None, error.to_string()),
+		}
+
+		match self.git.branch_exists(path, branch) {
+			Ok(true) => return failed(None, format!("Branch {branch} already exists")),
+			Ok(false) => {}
+			Err(error) => return failed(None, error.to_string()),
+		}
+
+		if let Err(error) = self.git.create_branch(path, branch) {
+			// Creating and checking out can fail halfway; a branch left behind is ours to abandon
+			let created = self.git.branch_exists(path, branch).unwrap_or(true);
+			return failed(created.then_some(base_branch), error.to_string());
+		}
+
+		let applied = match change {
+			BatchChange::Patch(patch) => self.git.apply_patch(path, patch).map_err(|e| e.to_string()),
+
+			BatchChange::Script(script) => match repo_exec(path, script, &repo_env(repo), &ExecOptions::default()) {
+				Ok(exit_status) if exit_status.success() => Ok(()),
+				Ok(exit_status) => Err(format!("Script exited with {exit_status}")),
+				Err(error) => Err(error.to_string()),
+			},
+		};
+
+		if let Err(error) = applied {
+			return match self.restore(path, &base_branch, branch) {
+				Ok(()) => failed(None, error),
+				Err(restore_error) => failed(Some(base_branch), format!("{error}; restoring failed: {restore_error}")),
+			};
+		}
+
+		match self.git.commit_all(path, message) {
+			Ok(true) => BatchRepoState {
+				status: BatchRepoStatus::Changed,
+				base_branch: Some(base_branch),
+				error: None,
+			},
+
+			// Nothing changed: leave the repo as we found it
+			Ok(false) => {
+				match self.restore(path, &base_branch, branch) {
+					Ok(()) => BatchRepoState {
+						status: BatchRepoStatus::NoOp,
+						base_branch: None,
+						error: None,
+					},
+					Err(error) => failed(Some(base_branch), error.to_string()),
+				}
+			}
+
+			Err(error) => failed(Some(base_branch), error.to_string()),
+		}
+	}
+
+	/// Drops whatever the change left in the working tree and deletes `branch`, switching
+	/// back to `base_branch`.
+	fn restore(&self, path: &Path, base_branch: &str, branch: &str) -> Result<(), GitError> {
+		self.git.discard_changes(path)?;
+		self.git.checkout_branch(path, base_branch)?;
+		self.git.delete_branch(path, branch)
+	}
+
+	fn save(&self, state: &BatchState) -> Result<(), BatchError> {
+		let state_toml = toml::to_string(state).map_err(BatchError::Serialize)?;
+		self.storage.save(state_toml).map_err(BatchError::Save)
+	}
+
+	fn load(&self) -> Result<Option<BatchState>, BatchError> {
+		if !self.storage.exists() {
+			return Ok(None);
+		}
+
+		let state_toml = self.storage.read();
+		toml::from_str(&state_toml).map(Some).map_err(BatchError::Parse)
+	}
+}
@@ -0,0 +1,238 @@
+use crate::commit_log::VaqCommit;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde_derive::Serialize;
+
+/// Conventional Commit types in the order their sections are rendered, with their titles.
+/// Anything else ends up under "Other Changes".
+const SECTIONS: &[(&str, &str)] = &[
+	("feat", "Features"),
+	("fix", "Bug Fixes"),
+	("perf", "Performance"),
+	("refactor", "Refactoring"),
+	("revert", "Reverts"),
+	("docs", "Documentation"),
+	("test", "Tests"),
+	("build", "Build"),
+	("ci", "CI"),
+	("style", "Style"),
+	("chore", "Chores"),
+];
+
+const OTHER: &str = "other";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ChangelogEntry {
+	pub repo: String,
+	pub id: String,
+
+	#[serde(rename = "type")]
+	pub kind: String,
+
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub scope: Option<String>,
+
+	pub breaking: bool,
+	pub description: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ChangelogSection {
+	#[serde(rename = "type")]
+	pub kind: String,
+	pub title: String,
+
+	/// Entries grouped by repo name.
+	pub repos: BTreeMap<String, Vec<ChangelogEntry>>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Changelog {
+	pub sections: Vec<ChangelogSection>,
+}
+
+impl ChangelogEntry {
+	/// Parses a Conventional Commit summary (`type(scope)!: description`).
+	/// Summaries not following the convention are kept verbatim with type `other`.
+	pub fn from_commit(commit: &VaqCommit) -> Self {
+		let entry = |kind: &str, scope: Option<String>, breaking: bool, description: &str| ChangelogEntry {
+			repo: commit.repo.clone(),
+			id: commit.short_id().to_string(),
+			kind: kind.to_string(),
+			scope,
+			breaking: breaking || commit.message.contains("BREAKING CHANGE:"),
+			description: description.trim().to_string(),
+		};
+
+		let summary = commit.summary.as_str();
+
+		let Some((prefix, description)) = summary.split_once(':') else {
+			return entry(OTHER, None, false, summary);
+		};
+
+		let (prefix, breaking) = match prefix.strip_suffix('!') {
+			Some(prefix) => (prefix, true),
+			None => (prefix, false),
+		};
+
+		let (kind, scope) = match prefix.split_once('(') {
+			Some((kind, scope)) => match scope.strip_suffix(')') {
+				Some(scope) => (kind, Some(scope.to_string())),
+				None => return entry(OTHER, None, false, summary),
+			},
+			None => (prefix, None),
+		};
+
+		let is_type = !kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphabetic());
+		if !is_type {
+			return entry(OTHER, None, false, summary);
+		}
+
+		entry(&kind.to_lowercase(), scope, breaking, description)
+	}
+
+	fn to_markdown(&self) -> String {
+		let breaking = if self.breaking { "**BREAKING** " } else { "" };
+
+		match &self.scope {
+			Some(scope) => format!("- {breaking}**{scope}:** {} ({})", self.description, self.id),
+			None => format!("- {breaking}{} ({})", self.description, self.id),
+		}
+	}
+}
+
+impl Changelog {
+	pub fn new(commits: &[VaqCommit]) -> Self {
+		let mut by_kind: BTreeMap<String, BTreeMap<String, Vec<ChangelogEntry>>> = BTreeMap::new();
+
+		for commit in commits {
+			let mut entry = ChangelogEntry::from_commit(commit);
+
+			if !SECTIONS.iter().any(|(kind, _)| *kind == entry.kind) {
+				entry.kind = OTHER.to_string();
+			}
+
+			by_kind
+				.entry(entry.kind.clone())
+				.or_default()
+				.entry(entry.repo.clone())
+				.or_default()
+				.push(entry);
+		}
+
+		let sections = SECTIONS
+			.iter()
+			.chain(std::iter::once(&(OTHER, "Other Changes")))
+			.filter_map(|(kind, title)| {
+				by_kind.remove(*kind).map(|repos| ChangelogSection {
+					kind: kind.to_string(),
+					title: title.to_string(),
+					repos,
+				})
+			})
+			.collect();
+
+		Changelog { sections }
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.sections.is_empty()
+	}
+
+	pub fn to_markdown(&self) -> String {
+		let mut markdown = String::new();
+
+		for section in &self.sections {
+			writeln!(markdown, "## {}", section.title).expect("write to string");
+
+			for (repo, entries) in &section.repos {
+				writeln!(markdown).expect("write to string");
+				writeln!(markdown, "### {repo}").expect("write to string");
+				writeln!(markdown).expect("write to string");
+
+				for entry in entries {
+					writeln!(markdown, "{}", entry.to_markdown()).expect("write to string");
+				}
+			}
+
+			writeln!(markdown).expect("write to string");
+		}
+
+		markdown
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn commit(repo: &str, summary: &str) -> VaqCommit {
+		VaqCommit {
+			repo: repo.to_string(),
+			id: "0123456789abcdef".to_string(),
+			author_name: "Someone".to_string(),
+			author_email: "someone@example.org".to_string(),
+			time: 0,
+			offset_minutes: 0,
+			summary: summary.to_string(),
+			message: summary.to_string(),
+		}
+	}
+
+	#[test]
+	fn parse_conventional_commit() {
+		let entry = ChangelogEntry::from_commit(&commit("api", "feat(auth)!: drop basic auth"));
+
+		assert_eq!("feat", entry.kind);
+		assert_eq!(Some("auth".to_string()), entry.scope);
+		assert!(entry.breaking);
+		assert_eq!("drop basic auth", entry.description);
+		assert_eq!("0123456", entry.id);
+	}
+
+	#[test]
+	fn parse_non_conventional_commit() {
+		let entry = ChangelogEntry::from_commit(&commit("api", "Merge branch 'main': conflicts"));
+
+		assert_eq!("other", entry.kind);
+		assert_eq!(None, entry.scope);
+		assert_eq!("Merge branch 'main': conflicts", entry.description);
+	}
+
+	#[test]
+	fn markdown_groups_by_type_then_repo() {
+		let changelog = Changelog::new(&[
+			commit("web", "fix: typo"),
+			commit("api", "feat: add endpoint"),
+			commit("api", "wip"),
+			commit("web", "feat(ui): dark mode"),
+		]);
+
+		let expected = "## Features
+
+### api
+
+- add endpoint (0123456)
+
+### web
+
+- **ui:** dark mode (0123456)
+
+## Bug Fixes
+
+### web
+
+- typo (0123456)
+
+## Other Changes
+
+### api
+
+- wip (0123456)
+
+";
+		assert_eq!(expected, changelog.to_markdown());
+	}
+}
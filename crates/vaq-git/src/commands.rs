@@ -0,0 +1,57 @@
+use crate::exec::ExecOptions;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde_derive::{Deserialize, Serialize};
+
+/// A command registered in `.vaquera.toml`, run with `vaquera run <name>` or `vaquera <name>`:
+///
+/// ```toml
+/// [commands.test]
+/// run = "cargo test"
+/// description = "Run the tests of all Rust repos"
+/// tags = ["rust"]
+/// dir = "backend"
+/// env = { RUST_BACKTRACE = "1" }
+/// parallel = 4
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CustomCommand {
+	/// Command line, interpreted by the shell like a single `exec` argument.
+	pub run: String,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+
+	/// Repos to run in unless `--tag` is given, in the same syntax as `--tag` arguments.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub tags: Vec<String>,
+
+	/// Folder of each repo to run in, relative to the repo.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub dir: Option<PathBuf>,
+
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub env: BTreeMap<String, String>,
+
+	/// How many repos to run in at once.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub parallel: Option<usize>,
+}
+
+impl CustomCommand {
+	pub fn exec_args(&self) -> Vec<String> {
+		vec![self.run.clone()]
+	}
+
+	pub fn exec_options(&self, oneline: bool) -> ExecOptions {
+		ExecOptions {
+			oneline,
+			dir: self.dir.clone(),
+			env: self.env.clone(),
+			parallel: self.parallel.unwrap_or(1),
+			..Default::default()
+		}
+	}
+}
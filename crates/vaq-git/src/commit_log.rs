@@ -0,0 +1,171 @@
+use crate::git::{Git, GitError};
+use crate::repos::VaqRepo;
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, TimeZone, Utc};
+use serde_derive::Serialize;
+use thiserror::Error;
+
+/// What to walk in each repo. Mirrors the subset of `git log` options vaquera supports.
+#[derive(Clone, Debug, Default)]
+pub struct LogQuery {
+	/// Revision to start walking from; HEAD when omitted.
+	pub to: Option<String>,
+
+	/// Revision whose history is excluded, as in `git log <from>..<to>`.
+	pub from: Option<String>,
+
+	/// Unix timestamp; older commits are skipped.
+	pub since: Option<i64>,
+
+	/// Case-insensitive substring matched against author name and email.
+	pub author: Option<String>,
+
+	pub max_count: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct VaqCommit {
+	pub repo: String,
+	pub id: String,
+	pub author_name: String,
+	pub author_email: String,
+
+	/// Commit time as a unix timestamp, used to merge repos into one stream.
+	pub time: i64,
+
+	/// Offset from UTC of `time`, in minutes.
+	#[serde(skip)]
+	pub offset_minutes: i32,
+
+	pub summary: String,
+	pub message: String,
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum LogError {
+	#[error("Invalid date `{0}`, expected YYYY-MM-DD or e.g. \"2 weeks ago\"")]
+	InvalidDate(String),
+}
+
+/// Commits of all repos, newest first, plus the repos that couldn't be read.
+pub struct VaqLog {
+	pub commits: Vec<VaqCommit>,
+	pub failed: Vec<(PathBuf, GitError)>,
+}
+
+impl VaqCommit {
+	pub fn short_id(&self) -> &str {
+		&self.id[..self.id.len().min(7)]
+	}
+
+	/// Commit date in the committer's own timezone, e.g. `2024-05-01 14:03`.
+	pub fn date(&self) -> String {
+		let offset = FixedOffset::east_opt(self.offset_minutes * 60)
+			.unwrap_or_else(|| FixedOffset::east_opt(0).expect("zero offset is valid"));
+
+		offset.timestamp_opt(self.time, 0)
+			.single()
+			.map(|date_time| date_time.format("%Y-%m-%d %H:%M").to_string())
+			.unwrap_or_default()
+	}
+}
+
+/// Walks every repo and merges their commits into a single stream sorted by commit time.
+/// `max_count` applies to the merged stream, not to each repo.
+pub fn log(git: &dyn Git, repos: Vec<VaqRepo>, query: &LogQuery) -> VaqLog {
+	let mut commits: Vec<VaqCommit> = Vec::new();
+	let mut failed = Vec::new();
+
+	for repo in repos {
+		match git.log(repo.path.as_path(), query) {
+			Ok(repo_commits) => commits.extend(repo_commits.into_iter().map(|commit| VaqCommit {
+				repo: repo.name.clone(),
+				..commit
+			})),
+			Err(error) => failed.push((repo.path, error)),
+		}
+	}
+
+	commits.sort_by(|a, b| b.time.cmp(&a.time).then_with(|| a.repo.cmp(&b.repo)));
+
+	if let Some(max_count) = query.max_count {
+		commits.truncate(max_count);
+	}
+
+	VaqLog { commits, failed }
+}
+
+/// Parses `--since` values: an ISO date (`2024-05-01`), an RFC 3339 timestamp,
+/// or a relative expression such as `3 days ago`, `2 weeks` or `yesterday`.
+pub fn parse_since(text: &str) -> Result<i64, LogError> {
+	parse_since_at(text, Utc::now())
+}
+
+fn parse_since_at(text: &str, now: DateTime<Utc>) -> Result<i64, LogError> {
+	let invalid = || LogError::InvalidDate(text.to_string());
+	let trimmed = text.trim();
+
+	if let Ok(date) = NaiveDate::parse_from_str(trimmed, "%Y-%m-%d") {
+		let midnight = date.and_hms_opt(0, 0, 0).ok_or_else(invalid)?;
+		return Local.from_local_datetime(&midnight)
+			.earliest()
+			.map(|date_time| date_time.timestamp())
+			.ok_or_else(invalid);
+	}
+
+	if let Ok(date_time) = DateTime::parse_from_rfc3339(trimmed) {
+		return Ok(date_time.timestamp());
+	}
+
+	if trimmed == "yesterday" {
+		return Ok((now - Duration::days(1)).timestamp());
+	}
+
+	let relative = trimmed.strip_suffix("ago").unwrap_or(trimmed).trim();
+	let split = relative.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+	let (amount, unit) = relative.split_at(split);
+	let amount: i64 = amount.parse().map_err(|_| invalid())?;
+
+	let duration = match unit.trim().trim_end_matches('s') {
+		"m" | "min" | "minute" => Duration::minutes(amount),
+		"h" | "hour" => Duration::hours(amount),
+		"d" | "day" => Duration::days(amount),
+		"w" | "week" => Duration::weeks(amount),
+		"month" => Duration::days(amount * 30),
+		"y" | "year" => Duration::days(amount * 365),
+		_ => return Err(invalid()),
+	};
+
+	Ok((now - duration).timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_since_relative() {
+		let now = Utc.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap();
+		let day = 24 * 60 * 60;
+
+		assert_eq!(now.timestamp() - 3 * day, parse_since_at("3 days ago", now).unwrap());
+		assert_eq!(now.timestamp() - 14 * day, parse_since_at("2 weeks", now).unwrap());
+		assert_eq!(now.timestamp() - 2 * 60 * 60, parse_since_at("2h", now).unwrap());
+		assert_eq!(now.timestamp() - day, parse_since_at("yesterday", now).unwrap());
+	}
+
+	#[test]
+	fn parse_since_rfc3339() {
+		let now = Utc::now();
+		assert_eq!(1714521600, parse_since_at("2024-05-01T00:00:00Z", now).unwrap());
+	}
+
+	#[test]
+	fn parse_since_invalid() {
+		assert!(parse_since_at("last tuesday", Utc::now()).is_err());
+		assert!(parse_since_at("3 fortnights ago", Utc::now()).is_err());
+	}
+}
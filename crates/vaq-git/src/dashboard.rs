@@ -0,0 +1,313 @@
+use crate::git::{Git, VaqRepoStatus};
+use crate::repos::VaqRepo;
+use crate::tag_filter::TagFilter;
+
+use std::path::PathBuf;
+
+/// Key presses the dashboard reacts to, independent of the terminal library.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+	Up,
+	Down,
+	Enter,
+	Esc,
+	Backspace,
+	Char(char),
+}
+
+/// What the terminal has to do after a key press: things the dashboard can't do while it owns the screen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Effect {
+	None,
+	Quit,
+	/// Open an interactive shell in the repo.
+	Shell(PathBuf),
+	/// Run a command in the repo and show its output.
+	Run { path: PathBuf, command: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Mode {
+	List,
+	/// Editing the tag filter.
+	Filter,
+	/// Typing a command to run in the selected repo.
+	Command(String),
+	Details,
+}
+
+pub struct DashboardRow {
+	pub repo: VaqRepo,
+	pub status: Result<VaqRepoStatus, String>,
+}
+
+/// State of the `ui` dashboard. It is driven by key presses and renders to plain lines of text,
+/// so it can be tested without a terminal, and does its git work through the `Git` trait.
+///
+/// Keys: up/down (or k/j) select, `/` edits the tag filter (same syntax as `--tag`, with spaces
+/// between OR groups), `f` fetches, `p` pulls, `s` opens a shell, `:` runs a command, enter shows
+/// details, `r` refreshes and `q` quits.
+pub struct Dashboard<'g> {
+	git: &'g dyn Git,
+	rows: Vec<DashboardRow>,
+	filter: String,
+	selected: usize,
+	mode: Mode,
+	message: String,
+}
+
+const HELP: &str = "/ filter  f fetch  p pull  s shell  : run  enter details  r refresh  q quit";
+
+impl<'g> Dashboard<'g> {
+	pub fn new(git: &'g dyn Git, repos: Vec<VaqRepo>) -> Self {
+		let rows = repos
+			.into_iter()
+			.map(|repo| DashboardRow { status: read_status(git, &repo), repo })
+			.collect();
+
+		Dashboard { git, rows, filter: String::new(), selected: 0, mode: Mode::List, message: String::new() }
+	}
+
+	/// Rows matching the tag filter.
+	pub fn visible(&self) -> Vec<&DashboardRow> {
+		let filter = self.tag_filter();
+		self.rows.iter().filter(|row| filter.matches(&row.repo.tags)).collect()
+	}
+
+	pub fn selected(&self) -> Option<&DashboardRow> {
+		self.visible().get(self.selected).copied()
+	}
+
+	pub fn message(&self) -> &str {
+		&self.message
+	}
+
+	pub fn handle_key(&mut self, key: Key) -> Effect {
+		match self.mode.clone() {
+			Mode::Filter => self.edit_filter(key),
+			Mode::Command(command) => return self.edit_command(command, key),
+			Mode::Details => {
+				if matches!(key, Key::Esc | Key::Enter | Key::Char('q')) {
+					self.mode = Mode::List;
+				}
+			}
+			Mode::List => return self.list_key(key),
+		}
+
+		Effect::None
+	}
+
+	/// Re-reads the status of the selected repo, e.g. after a shell or command ran in it.
+	pub fn refresh_selected(&mut self) {
+		let Some(path) = self.selected().map(|row| row.repo.path.clone()) else {
+			return;
+		};
+
+		if let Some(row) = self.rows.iter_mut().find(|row| row.repo.path == path) {
+			row.status = read_status(self.git, &row.repo);
+		}
+	}
+
+	pub fn set_message(&mut self, message: String) {
+		self.message = message;
+	}
+
+	fn list_key(&mut self, key: Key) -> Effect {
+		let count = self.visible().len();
+
+		match key {
+			Key::Up | Key::Char('k') => self.selected = self.selected.saturating_sub(1),
+			Key::Down | Key::Char('j') => self.selected = (self.selected + 1).min(count.saturating_sub(1)),
+			Key::Char('/') => self.mode = Mode::Filter,
+			Key::Char('r') => {
+				for row in &mut self.rows {
+					row.status = read_status(self.git, &row.repo);
+				}
+				self.message = "Refreshed".to_string();
+			}
+			Key::Char('q') | Key::Esc => return Effect::Quit,
+			_ => {}
+		}
+
+		let Some(path) = self.selected().map(|row| row.repo.path.clone()) else {
+			return Effect::None;
+		};
+
+		match key {
+			Key::Enter => self.mode = Mode::Details,
+			Key::Char('f') => {
+				self.message = match self.git.fetch(&path) {
+					Ok(()) => format!("Fetched {}", path.display()),
+					Err(error) => error.to_string(),
+				};
+				self.refresh_selected();
+			}
+			Key::Char('p') => {
+				self.message = match self.git.pull(&path) {
+					Ok(()) => format!("Pulled {}", path.display()),
+					Err(error) => error.to_string(),
+				};
+				self.refresh_selected();
+			}
+			Key::Char('s') => return Effect::Shell(path),
+			Key::Char(':') => self.mode = Mode::Command(String::new()),
+			_ => {}
+		}
+
+		Effect::None
+	}
+
+	fn edit_filter(&mut self, key: Key) {
+		match key {
+			Key::Char(c) => self.filter.push(c),
+			Key::Backspace => {
+				self.filter.pop();
+			}
+			Key::Enter | Key::Esc => self.mode = Mode::List,
+			_ => {}
+		}
+
+		self.selected = self.selected.min(self.visible().len().saturating_sub(1));
+	}
+
+	fn edit_command(&mut self, mut command: String, key: Key) -> Effect {
+		match key {
+			Key::Char(c) => command.push(c),
+			Key::Backspace => {
+				command.pop();
+			}
+			Key::Esc => {
+				self.mode = Mode::List;
+				return Effect::None;
+			}
+			Key::Enter => {
+				self.mode = Mode::List;
+				return match self.selected() {
+					Some(row) if !command.trim().is_empty() => Effect::Run { path: row.repo.path.clone(), command },
+					_ => Effect::None,
+				};
+			}
+			_ => {}
+		}
+
+		self.mode = Mode::Command(command);
+		Effect::None
+	}
+
+	/// Groups separated by spaces are ORed, tags within a group separated by commas are ANDed,
+	/// like repeated `--tag` arguments. Dangling commas, e.g. while typing, are ignored.
+	fn tag_filter(&self) -> TagFilter {
+		let groups: Vec<String> = self.filter
+			.split_whitespace()
+			.map(|group| group.split(',').filter(|tag| !tag.is_empty()).collect::<Vec<_>>().join(","))
+			.filter(|group| !group.is_empty())
+			.collect();
+
+		TagFilter::from_cli_args(&groups)
+	}
+
+	/// The screen as lines of at most `width` characters, `height` lines in total.
+	pub fn render(&self, width: usize, height: usize) -> Vec<String> {
+		let mut lines = match self.mode {
+			Mode::Details => self.render_details(),
+			_ => self.render_list(height.saturating_sub(3)),
+		};
+
+		lines.truncate(height.saturating_sub(2));
+		lines.resize(height.saturating_sub(2), String::new());
+
+		lines.push(match &self.mode {
+			Mode::Filter => format!("filter: {}_", self.filter),
+			Mode::Command(command) => format!("run in {}: {}_", self.selected_path(), command),
+			_ if self.filter.is_empty() => String::new(),
+			_ => format!("filter: {}", self.filter),
+		});
+		lines.push(if self.message.is_empty() { HELP.to_string() } else { self.message.clone() });
+
+		lines.into_iter().map(|line| line.chars().take(width).collect()).collect()
+	}
+
+	fn selected_path(&self) -> String {
+		self.selected().map(|row| row.repo.path.display().to_string()).unwrap_or_default()
+	}
+
+	fn render_list(&self, rows_height: usize) -> Vec<String> {
+		let visible = self.visible();
+		let path_width = visible.iter().map(|row| row.repo.path.as_os_str().len()).max().unwrap_or(0).max(4);
+		let columns = |path: &str, branch: &str, state: &str, sync: &str, tags: &str| {
+			format!("{path:<path_width$}  {branch:<20}  {state:<11}  {sync:<9}  {tags}")
+		};
+
+		let mut lines = vec![format!("  {}", columns("REPO", "BRANCH", "STATE", "SYNC", "TAGS"))];
+
+		if visible.is_empty() {
+			lines.push("  No repos match the filter".to_string());
+		}
+
+		let offset = (self.selected + 1).saturating_sub(rows_height.max(1));
+
+		for (index, row) in visible.iter().enumerate().skip(offset).take(rows_height.max(1)) {
+			let (branch, state, sync) = match &row.status {
+				Ok(status) => describe(status),
+				Err(error) => (String::new(), error.clone(), String::new()),
+			};
+			let marker = if index == self.selected { "> " } else { "  " };
+			let line = columns(&row.repo.path.display().to_string(), &branch, &state, &sync, &row.repo.tags.join(","));
+
+			lines.push(format!("{marker}{line}"));
+		}
+
+		lines
+	}
+
+	fn render_details(&self) -> Vec<String> {
+		let Some(row) = self.selected() else {
+			return Vec::new();
+		};
+
+		let mut lines = vec![
+			format!("Path:    {}", row.repo.path.display()),
+			format!("Name:    {}", row.repo.name),
+			format!("Tags:    {}", if row.repo.tags.is_empty() { "(none)".to_string() } else { row.repo.tags.join(", ") }),
+		];
+
+		match &row.status {
+			Ok(status) => {
+				let (branch, state, sync) = describe(status);
+				lines.push(format!("Branch:  {branch}"));
+				lines.push(format!("Tracks:  {}", status.upstream.as_deref().unwrap_or("(none)")));
+				lines.push(format!("State:   {state} {sync}"));
+			}
+			Err(error) => lines.push(format!("State:   {error}")),
+		}
+
+		lines.push("Remotes:".to_string());
+		if row.repo.remotes.is_empty() {
+			lines.push("  (none)".to_string());
+		}
+		for (name, remote) in row.repo.remotes.iter() {
+			lines.push(format!("  {}: {}", name, remote.url));
+		}
+
+		lines
+	}
+}
+
+fn read_status(git: &dyn Git, repo: &VaqRepo) -> Result<VaqRepoStatus, String> {
+	git.status(&repo.path)
+		.map_err(|_| if repo.path.exists() { "not a git repo" } else { "missing" }.to_string())
+}
+
+/// Branch, clean/dirty and ahead/behind columns.
+fn describe(status: &VaqRepoStatus) -> (String, String, String) {
+	let branch = status.branch.clone().unwrap_or_else(|| "(detached)".to_string());
+	let state = if status.dirty { "dirty" } else { "clean" }.to_string();
+	let sync = match (status.ahead, status.behind) {
+		(0, 0) => String::new(),
+		(ahead, 0) => format!("↑{ahead}"),
+		(0, behind) => format!("↓{behind}"),
+		(ahead, behind) => format!("↑{ahead} ↓{behind}"),
+	};
+
+	(branch, state, sync)
+}
@@ -0,0 +1,197 @@
+use crate::git::Git;
+use crate::repos::VaqRepo;
+use crate::settings::VaqSettings;
+use crate::sync::{diff_remotes, RemoteChange};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use derive_more::Display;
+
+/// How deep below the workspace root to look for git repos missing from the config.
+const UNTRACKED_SEARCH_DEPTH: usize = 4;
+
+/// A disagreement between `.vaquera.toml` and what is on disk.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+pub enum VaqIssue {
+	#[display("{}: repo folder missing", path.display())]
+	MissingFolder { path: PathBuf },
+
+	#[display("{}: not a git repository", path.display())]
+	NotGitRepo { path: PathBuf },
+
+	#[display("{}: cannot read remotes from git ({error})", path.display())]
+	RemotesUnreadable { path: PathBuf, error: String },
+
+	#[display("{}: remotes differ from config ({})", path.display(), differences.join(", "))]
+	RemotesDiffer { path: PathBuf, differences: Vec<String> },
+
+	#[display("duplicate repo name `{name}` used by {}", paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", "))]
+	DuplicateName { name: String, paths: Vec<PathBuf> },
+
+	#[display("{}: path is outside the workspace", path.display())]
+	OutsideWorkspace { path: PathBuf },
+
+	#[display("{}: git repository not in config", path.display())]
+	UntrackedRepo { path: PathBuf },
+}
+
+/// Checks every configured repo against the filesystem below `workspace_root`, and looks
+/// for git repos under the root that the config doesn't know about. Remotes are compared
+/// with the `url_rewrites` from `settings` applied, as they were when cloning.
+pub fn diagnose(git: &dyn Git, repos: &[VaqRepo], settings: &VaqSettings, workspace_root: &Path) -> Vec<VaqIssue> {
+	let mut issues = Vec::new();
+
+	for repo in repos {
+		let full_path = workspace_root.join(&repo.path);
+
+		if !is_inside(workspace_root, &full_path) {
+			issues.push(VaqIssue::OutsideWorkspace { path: repo.path.clone() });
+		}
+
+		if !full_path.is_dir() {
+			issues.push(VaqIssue::MissingFolder { path: repo.path.clone() });
+			continue;
+		}
+
+		if !is_git_repo(&full_path) {
+			issues.push(VaqIssue::NotGitRepo { path: repo.path.clone() });
+			continue;
+		}
+
+		let remotes = git.read_all_remotes(full_path.as_path())
+			.map_err(|e| e.to_string())
+			.and_then(|disk_remotes| {
+				let push_urls = git.read_push_urls(full_path.as_path()).map_err(|e| e.to_string())?;
+				Ok((disk_remotes, push_urls))
+			});
+
+		match remotes {
+			Ok((disk_remotes, push_urls)) => {
+				let config_remotes = settings.rewrite_remotes(&repo.remotes);
+				let differences: Vec<String> = diff_remotes(&config_remotes, &disk_remotes, &push_urls)
+					.iter()
+					.map(describe_difference)
+					.collect();
+
+				if !differences.is_empty() {
+					issues.push(VaqIssue::RemotesDiffer { path: repo.path.clone(), differences });
+				}
+			}
+
+			Err(error) => {
+				issues.push(VaqIssue::RemotesUnreadable { path: repo.path.clone(), error });
+			}
+		}
+	}
+
+	let mut paths_by_name: BTreeMap<&str, Vec<PathBuf>> = BTreeMap::new();
+	for repo in repos {
+		paths_by_name.entry(repo.name.as_str()).or_default().push(repo.path.clone());
+	}
+
+	for (name, paths) in paths_by_name {
+		if paths.len() > 1 {
+			issues.push(VaqIssue::DuplicateName { name: name.to_string(), paths });
+		}
+	}
+
+	let known: BTreeSet<PathBuf> = repos
+		.iter()
+		.map(|repo| normalize_lexically(&workspace_root.join(&repo.path)))
+		.collect();
+
+	let mut untracked = Vec::new();
+	find_untracked_repos(workspace_root, workspace_root, &known, UNTRACKED_SEARCH_DEPTH, &mut untracked);
+	untracked.sort();
+
+	issues.extend(untracked.into_iter().map(|path| VaqIssue::UntrackedRepo { path }));
+	issues
+}
+
+fn describe_difference(change: &RemoteChange) -> String {
+	match change {
+		RemoteChange::Added { name, .. } => format!("`{name}` only in config"),
+		RemoteChange::Removed { name, .. } => format!("`{name}` only in git"),
+		RemoteChange::Changed { name, git_url, config_url } => {
+			format!("`{name}` is {config_url} in config but {git_url} in git")
+		}
+		RemoteChange::PushUrlDiffers { name, push_url, .. } => format!("`{name}` pushes to {push_url} in git"),
+	}
+}
+
+pub(crate) fn is_git_repo(path: &Path) -> bool {
+	// `.git` is a file rather than a folder in worktrees and submodules
+	path.join(".git").exists()
+}
+
+fn find_untracked_repos(
+	workspace_root: &Path,
+	dir: &Path,
+	known: &BTreeSet<PathBuf>,
+	depth: usize,
+	found: &mut Vec<PathBuf>,
+) {
+	let Ok(entries) = fs::read_dir(dir) else {
+		return;
+	};
+
+	for entry in entries.flatten() {
+		let path = entry.path();
+		let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+
+		if is_hidden || !path.is_dir() {
+			continue;
+		}
+
+		// Don't descend into repos, known or not: nested repos are the repo's own business
+		if is_git_repo(&path) {
+			if !known.contains(&normalize_lexically(&path)) {
+				let relative = path.strip_prefix(workspace_root).unwrap_or(&path);
+				found.push(relative.to_path_buf());
+			}
+			continue;
+		}
+
+		if depth > 1 {
+			find_untracked_repos(workspace_root, &path, known, depth - 1, found);
+		}
+	}
+}
+
+fn is_inside(workspace_root: &Path, path: &Path) -> bool {
+	normalize_lexically(path).starts_with(normalize_lexically(workspace_root))
+}
+
+/// Resolves `.` and `..` without touching the filesystem, so missing folders can be checked too.
+fn normalize_lexically(path: &Path) -> PathBuf {
+	let mut normalized = PathBuf::new();
+
+	for component in path.components() {
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir => {
+				normalized.pop();
+			}
+			other => normalized.push(other),
+		}
+	}
+
+	normalized
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn outside_workspace() {
+		let root = Path::new("/work/space");
+
+		assert!(is_inside(root, &root.join("repo")));
+		assert!(is_inside(root, &root.join("nested/../repo")));
+		assert!(!is_inside(root, &root.join("../elsewhere")));
+		assert!(!is_inside(root, &root.join("/tmp/repo")));
+	}
+}
@@ -1,56 +1,377 @@
+use crate::git::Git;
+use crate::hooks::{self, HookEvent, Hooks};
+use crate::report::{self, RepoResult, ReportFormat, ResultStatus};
 use crate::repos::VaqRepo;
+use crate::template;
 
+use std::collections::BTreeMap;
 use std::env;
+use std::fs;
 use std::io::{BufRead, BufReader, Error, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+#[cfg(unix)]
+use std::sync::Once;
+#[cfg(unix)]
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::thread;
+use std::time::{Duration, Instant};
 
-pub fn exec(exec_args: Vec<String>, repos: Vec<VaqRepo>, oneline: bool) {
-	let mut error_count = 0;
-	let mut skipped_count = 0;
-	for repo in &repos {
-		if !exists(&repo.path) {
-			if oneline {
-				println!("{}\tRepo folder missing, skipped.", repo.path.display());
-			} else {
-				println!();
-				println!("🏢 {}> Repo folder missing, skipped.", repo.path.display());
-			}
-			skipped_count += 1;
-			continue;
-		}
-		if oneline {
-			let (output, success) =
-				repo_exec_oneline(&repo.path, &exec_args).expect("Failed to execute command.");
-			match output {
-				Some(output_text) => println!("{}\t{}", repo.path.display(), output_text),
-				None => println!("{}\t", repo.path.display()),
-			}
-			if !success {
-				error_count += 1;
+use derive_more::Display;
+
+/// How `exec` runs a command in each repo.
+#[derive(Clone, Debug, Default)]
+pub struct ExecOptions {
+	/// Print one line per repo instead of the full output.
+	pub oneline: bool,
+	/// Run in this folder of each repo instead of its root.
+	pub dir: Option<PathBuf>,
+	/// Extra environment variables for the command.
+	pub env: BTreeMap<String, String>,
+	/// How many repos to run in at once; 0 or 1 runs them one after another. Output of a
+	/// repo is printed in one piece when its command finishes.
+	pub parallel: usize,
+
+	/// Run around the command in each repo, see `HookEvent::PreExec` and `HookEvent::PostExec`.
+	pub hooks: Hooks,
+	/// The shell running the command, or none.
+	pub interpreter: Interpreter,
+
+	/// Kill the command in a repo, with the processes it started, after running this long.
+	pub timeout: Option<Duration>,
+	/// Start no more repos once a command failed.
+	pub fail_fast: bool,
+	/// How often to run a failed command again, waiting `retry_delay` before each retry.
+	pub retries: usize,
+	pub retry_delay: Duration,
+
+	/// Also write the stdout and stderr of each repo to files in this folder, named by
+	/// `report::log_file_name`.
+	pub log_dir: Option<PathBuf>,
+	/// Print a table of the results to stderr at the end, see `report::summary_table`.
+	pub summary: bool,
+	/// Write a report of the results in this format, to `report_file` or else the format's
+	/// default file.
+	pub report: Option<ReportFormat>,
+	pub report_file: Option<PathBuf>,
+}
+
+/// How a command run in a repo ended.
+#[derive(Clone, Copy, Debug, Display)]
+pub enum RunStatus {
+	#[display("{_0}")]
+	Exited(ExitStatus),
+
+	/// Killed after running longer than `ExecOptions::timeout`.
+	#[display("timed out after {_0:?}")]
+	TimedOut(Duration),
+}
+
+impl RunStatus {
+	pub fn success(&self) -> bool {
+		matches!(self, RunStatus::Exited(status) if status.success())
+	}
+}
+
+/// A command that ran to its end: how it ended, what it wrote, and the text to print for it
+/// where the output isn't streamed.
+struct Finished {
+	status: RunStatus,
+	stdout: String,
+	stderr: String,
+	text: Option<String>,
+}
+
+pub fn exec(git: &(dyn Git + Sync), exec_args: Vec<String>, repos: Vec<VaqRepo>, options: &ExecOptions) {
+	let mut results: Vec<(usize, RepoResult)> = if options.parallel > 1 {
+		let next = AtomicUsize::new(0);
+		let stop = AtomicBool::new(false);
+		let results = Mutex::new(Vec::new());
+
+		thread::scope(|scope| {
+			for _ in 0..options.parallel.min(repos.len()) {
+				scope.spawn(|| {
+					while !stop.load(Ordering::Relaxed) {
+						let index = next.fetch_add(1, Ordering::Relaxed);
+						let Some(repo) = repos.get(index) else {
+							break;
+						};
+
+						let result = exec_in_repo(git, repo, &exec_args, options, true);
+						if options.fail_fast && is_failure(&result) {
+							stop.store(true, Ordering::Relaxed);
+						}
+						results.lock().expect("exec thread panicked").push((index, result));
+					}
+				});
 			}
-		} else {
-			let exit_status =
-				repo_exec(&repo.path, &exec_args).expect("Failed to execute command.");
-			if !exit_status.success() {
-				error_count += 1
+		});
+
+		results.into_inner().expect("exec thread panicked")
+	} else {
+		let mut results = Vec::new();
+
+		for (index, repo) in repos.iter().enumerate() {
+			let result = exec_in_repo(git, repo, &exec_args, options, false);
+			let failed = is_failure(&result);
+			results.push((index, result));
+
+			if options.fail_fast && failed {
+				break;
 			}
-			println!();
+		}
+
+		results
+	};
+
+	results.sort_by_key(|(index, _)| *index);
+	let mut results: Vec<RepoResult> = results.into_iter().map(|(_, result)| result).collect();
+
+	for repo in repos.iter().skip(results.len()) {
+		results.push(RepoResult {
+			path: repo.path.clone(),
+			status: ResultStatus::NotRun,
+			exit_code: None,
+			duration: Duration::ZERO,
+			attempts: 0,
+			first_error: None,
+		});
+	}
+
+	if options.summary {
+		eprint!("\n{}", report::summary_table(&results));
+	}
+
+	if let Some(format) = options.report {
+		let file = options.report_file.clone().unwrap_or_else(|| format.default_file());
+
+		if let Err(error) = fs::write(&file, format.render(&results)) {
+			eprintln!("Warning: Could not write report to {}. {error}", file.display());
 		}
 	}
-	if error_count > 0 || skipped_count > 0 {
+
+	let count = |status: ResultStatus| results.iter().filter(|result| result.status == status).count();
+	let error_count = count(ResultStatus::Failed);
+	let timed_out_count = count(ResultStatus::TimedOut);
+	let skipped_count = count(ResultStatus::Skipped);
+	let not_run_count = count(ResultStatus::NotRun);
+	let retried_count = results.iter().filter(|result| result.status == ResultStatus::Passed && result.attempts > 1).count();
+
+	if retried_count > 0 {
+		eprintln!("{retried_count} commands succeeded after retrying");
+	}
+
+	if error_count > 0 || timed_out_count > 0 || skipped_count > 0 || not_run_count > 0 {
 		if error_count > 0 {
 			eprintln!("{error_count} commands exited with non-zero status code");
 		}
+		if timed_out_count > 0 {
+			eprintln!("{timed_out_count} commands timed out");
+		}
 		if skipped_count > 0 {
 			eprintln!("{skipped_count} repos skipped");
 		}
+		if not_run_count > 0 {
+			eprintln!("{not_run_count} repos not run after a failure");
+		}
 		std::process::exit(1);
 	}
 }
 
-fn exists(repo_path: &Path) -> bool {
+fn is_failure(result: &RepoResult) -> bool {
+	matches!(result.status, ResultStatus::Failed | ResultStatus::TimedOut)
+}
+
+fn exec_in_repo(git: &dyn Git, repo: &VaqRepo, exec_args: &[String], options: &ExecOptions, buffered: bool) -> RepoResult {
+	let started = Instant::now();
+	let dir = match &options.dir {
+		Some(dir) => repo.path.join(dir),
+		None => repo.path.clone(),
+	};
+
+	let mut result = RepoResult {
+		path: dir.clone(),
+		status: ResultStatus::Failed,
+		exit_code: None,
+		duration: Duration::ZERO,
+		attempts: 0,
+		first_error: None,
+	};
+
+	let missing = if !exists(&repo.path) {
+		Some("Repo folder missing")
+	} else if !exists(&dir) {
+		Some("Folder missing")
+	} else {
+		None
+	};
+
+	if let Some(missing) = missing {
+		if options.oneline {
+			println!("{}\t{missing}, skipped.", dir.display());
+		} else {
+			println!("\n🏢 {}> {missing}, skipped.", dir.display());
+		}
+		result.status = ResultStatus::Skipped;
+		result.first_error = Some(missing.to_string());
+		return result;
+	}
+
+	if let Err(error) = hooks::run(&options.hooks, HookEvent::PreExec, repo, &BTreeMap::new()) {
+		eprintln!("Warning: {error}, command skipped");
+		result.first_error = Some(error.to_string());
+		return result;
+	}
+
+	let (exec_args, env) = expand_for_repo(git, repo, exec_args, &options.env);
+
+	let finished = loop {
+		result.attempts += 1;
+
+		// Without a shell, a misspelt program fails to start rather than exiting with an error
+		let finished = if options.oneline {
+			repo_exec_oneline(&dir, &exec_args, &env, options)
+		} else if buffered {
+			repo_exec_buffered(&dir, &exec_args, &env, options)
+		} else {
+			repo_exec_streaming(&dir, &exec_args, &env, options)
+		};
+
+		let failed_status = finished.as_ref().ok().map(|finished| finished.status).filter(|status| !status.success());
+
+		match failed_status {
+			Some(status) if result.attempts <= options.retries => {
+				eprintln!(
+					"Warning: {} in {}, retry {} of {}",
+					describe_failure(status),
+					dir.display(),
+					result.attempts,
+					options.retries
+				);
+				thread::sleep(options.retry_delay);
+			}
+			_ => break finished,
+		}
+	};
+
+	match finished {
+		Ok(finished) => {
+			match &finished.text {
+				Some(text) => println!("{text}"),
+				None => println!(),
+			}
+
+			if let Some(log_dir) = &options.log_dir {
+				write_logs(log_dir, &dir, &finished);
+			}
+
+			result.status = match finished.status {
+				status if status.success() => ResultStatus::Passed,
+				RunStatus::TimedOut(_) => ResultStatus::TimedOut,
+				RunStatus::Exited(_) => ResultStatus::Failed,
+			};
+			result.exit_code = match finished.status {
+				RunStatus::Exited(status) => status.code(),
+				RunStatus::TimedOut(_) => None,
+			};
+			result.first_error = match finished.status {
+				status if status.success() => None,
+				RunStatus::TimedOut(_) => Some(describe_failure(finished.status)),
+				RunStatus::Exited(_) => finished.stderr.lines().map(str::trim).find(|line| !line.is_empty()).map(str::to_string),
+			};
+		}
+		Err(error) => {
+			eprintln!("Warning: Failed to run command in {}. {error}", dir.display());
+			result.first_error = Some(error.to_string());
+		}
+	}
+
+	// Commands without an exit code timed out, were killed by a signal or didn't start
+	let exit_code = result.exit_code.unwrap_or(1);
+	let exit_code = BTreeMap::from([("VAQUERA_EXIT_CODE".to_string(), exit_code.to_string())]);
+	if let Err(error) = hooks::run(&options.hooks, HookEvent::PostExec, repo, &exit_code) {
+		eprintln!("Warning: {error}");
+	}
+
+	result.duration = started.elapsed();
+	result
+}
+
+fn write_logs(log_dir: &Path, path: &Path, finished: &Finished) {
+	let written = fs::create_dir_all(log_dir)
+		.and_then(|_| fs::write(log_dir.join(report::log_file_name(path, "stdout")), &finished.stdout))
+		.and_then(|_| fs::write(log_dir.join(report::log_file_name(path, "stderr")), &finished.stderr));
+
+	if let Err(error) = written {
+		eprintln!("Warning: Could not write logs of {} to {}. {error}", path.display(), log_dir.display());
+	}
+}
+
+/// The arguments and environment to run `exec_args` with in `repo`: placeholders in the
+/// arguments are expanded as by `template::expand`, plus `{branch}` with the current branch from
+/// `git`, and `env` is added to the `VAQUERA_REPO_*` variables of `repo_env`.
+///
+/// Values are substituted as they are, so in a single argument run by the shell a value with
+/// spaces or quotes needs quoting, e.g. `'{meta.title}'`, or the variable `"$VAQUERA_REPO_NAME"`.
+pub fn expand_for_repo(
+	git: &dyn Git,
+	repo: &VaqRepo,
+	exec_args: &[String],
+	env: &BTreeMap<String, String>,
+) -> (Vec<String>, BTreeMap<String, String>) {
+	let branch = if exec_args.iter().any(|arg| arg.contains("{branch}")) {
+		git.current_branch(&repo.path).ok()
+	} else {
+		None
+	};
+
+	let exec_args = exec_args
+		.iter()
+		.map(|arg| {
+			template::expand_with(arg, |placeholder| match placeholder {
+				"branch" => branch.clone(),
+				_ => template::value(placeholder, repo),
+			})
+		})
+		.collect();
+
+	let mut repo_env = repo_env(repo);
+	repo_env.extend(env.clone());
+
+	(exec_args, repo_env)
+}
+
+/// Environment describing `repo` for commands run in it: `VAQUERA_REPO_NAME`,
+/// `VAQUERA_REPO_PATH` (absolute), `VAQUERA_REPO_TAGS` (comma-separated), `VAQUERA_REPO_REMOTES`
+/// (comma-separated `name=url`) and `VAQUERA_REPO_REMOTE_<NAME>` with the URL of each remote.
+pub fn repo_env(repo: &VaqRepo) -> BTreeMap<String, String> {
+	let absolute_path = env::current_dir()
+		.map(|cwd| cwd.join(&repo.path))
+		.unwrap_or_else(|_| repo.path.clone());
+
+	let mut repo_env = BTreeMap::from([
+		("VAQUERA_REPO_NAME".to_string(), repo.name.clone()),
+		("VAQUERA_REPO_PATH".to_string(), absolute_path.display().to_string()),
+		("VAQUERA_REPO_TAGS".to_string(), repo.tags.join(",")),
+	]);
+
+	let remotes: Vec<String> = repo.remotes.iter().map(|(name, remote)| format!("{name}={}", remote.url)).collect();
+	repo_env.insert("VAQUERA_REPO_REMOTES".to_string(), remotes.join(","));
+
+	for (name, remote) in repo.remotes.iter() {
+		let variable_name: String = name
+			.chars()
+			.map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+			.collect();
+		repo_env.insert(format!("VAQUERA_REPO_REMOTE_{variable_name}"), remote.url.to_string());
+	}
+
+	repo_env
+}
+
+pub(crate) fn exists(repo_path: &Path) -> bool {
 	let mut current_path = env::current_dir().expect("failed to get current working directory");
 	current_path.push(repo_path);
 	current_path.exists() && current_path.is_dir()
@@ -150,71 +471,141 @@ fn quote_arg(arg: &str) -> String {
 	}
 }
 
-fn repo_exec(path: &Path, exec_args: &[String]) -> Result<ExitStatus, Error> {
-	println!();
-	println!("🏢 {}> {}", path.display(), format_args_for_display(exec_args));
+/// What runs the command of `exec` in each repo.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpreter {
+	/// `sh`, or `cmd` on Windows.
+	#[default]
+	System,
+	Bash,
+	Zsh,
+	Pwsh,
+	Nu,
+	/// No shell: the first argument is the program, run with the other arguments as they are.
+	Direct,
+}
 
-	// If single argument, pass directly to shell for interpretation (supports pipes, etc.)
-	// If multiple arguments, pass via positional parameters to avoid quoting issues
-	#[cfg(unix)]
-	let mut child_process: Child = if exec_args.len() == 1 {
-		Command::new("sh")
-			.arg("-c")
-			.arg(&exec_args[0]) // Single arg passed directly for shell interpretation
-			.current_dir(path)
-			.stdin(Stdio::null()) // Prevent interactive prompts/pagers
-			.stdout(Stdio::piped()) // Prevent TTY detection for pagers
-			.stderr(Stdio::piped())
-			.spawn()?
-	} else {
-		Command::new("sh")
-			.arg("-c")
-			.arg(r#""$@""#) // Execute all positional parameters
-			.arg("--") // $0 placeholder (ignored)
-			.args(exec_args) // These become $1, $2, $3, etc.
-			.current_dir(path)
-			.stdin(Stdio::null()) // Prevent interactive prompts/pagers
-			.stdout(Stdio::piped()) // Prevent TTY detection for pagers
-			.stderr(Stdio::piped())
-			.spawn()?
+/// The program and arguments running `exec_args` with `interpreter`. A single argument is
+/// a command line for the shell to interpret (supports pipes, etc.). Several arguments are
+/// passed on as they are: as positional parameters to POSIX shells, or quoted by the rules of
+/// the shell for the others, so each arrives as one argument of the program.
+fn command_line(interpreter: Interpreter, exec_args: &[String]) -> Vec<String> {
+	let posix = |shell: &str| {
+		let mut line = vec![shell.to_string(), "-c".to_string()];
+		if exec_args.len() == 1 {
+			line.push(exec_args[0].clone());
+		} else {
+			line.push(r#""$@""#.to_string()); // Execute all positional parameters
+			line.push("--".to_string()); // $0 placeholder (ignored)
+			line.extend(exec_args.iter().cloned()); // These become $1, $2, $3, etc.
+		}
+		line
 	};
 
-	#[cfg(windows)]
-	let mut child_process: Child = if exec_args.len() == 1 {
-		Command::new("cmd")
-			.arg("/C")
-			.arg(&exec_args[0]) // Single arg passed directly for shell interpretation
-			.current_dir(path)
-			.stdin(Stdio::null()) // Prevent interactive prompts/pagers
-			.stdout(Stdio::piped()) // Prevent TTY detection for pagers
-			.stderr(Stdio::piped())
-			.spawn()?
-	} else {
-		// Windows cmd doesn't have an equivalent to sh -c "$@"
-		// We need to join args with proper quoting
-		let command_string = exec_args
-			.iter()
-			.map(|arg| {
-				// Quote if contains spaces or special chars
-				if arg.contains(' ') || arg.contains('"') || arg.contains('&') || arg.contains('|')
-				{
-					format!("\"{}\"", arg.replace('"', "\"\""))
-				} else {
-					arg.clone()
-				}
-			})
-			.collect::<Vec<_>>()
-			.join(" ");
-		Command::new("cmd")
-			.arg("/C")
-			.arg(command_string)
-			.current_dir(path)
-			.stdin(Stdio::null()) // Prevent interactive prompts/pagers
-			.stdout(Stdio::piped()) // Prevent TTY detection for pagers
-			.stderr(Stdio::piped())
-			.spawn()?
+	let script = |prefix: &str, quote: fn(&str) -> String| {
+		if exec_args.len() == 1 {
+			exec_args[0].clone()
+		} else {
+			let quoted: Vec<String> = exec_args.iter().map(|arg| quote(arg)).collect();
+			format!("{prefix}{}", quoted.join(" "))
+		}
 	};
 
+	match interpreter {
+		#[cfg(not(windows))]
+		Interpreter::System => posix("sh"),
+		#[cfg(windows)]
+		Interpreter::System => vec!["cmd".to_string(), "/C".to_string(), script("", quote_cmd)],
+		Interpreter::Bash => posix("bash"),
+		Interpreter::Zsh => posix("zsh"),
+		// `&` calls the program even though its name is quoted
+		Interpreter::Pwsh => vec!["pwsh".to_string(), "-NoProfile".to_string(), "-Command".to_string(), script("& ", quote_pwsh)],
+		// `^` runs the external program rather than a nu command of the same name
+		Interpreter::Nu => vec!["nu".to_string(), "-c".to_string(), script("^", quote_nu)],
+		Interpreter::Direct => exec_args.to_vec(),
+	}
+}
+
+/// Arguments made of these characters need no quoting in any shell.
+fn is_plain(arg: &str) -> bool {
+	!arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:=,@+%".contains(c))
+}
+
+/// `cmd` has no escapes inside double quotes, but a doubled quote stands for one.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn quote_cmd(arg: &str) -> String {
+	if is_plain(arg) {
+		arg.to_string()
+	} else {
+		format!("\"{}\"", arg.replace('"', "\"\""))
+	}
+}
+
+/// PowerShell takes single-quoted strings literally, with a doubled single quote standing for one.
+fn quote_pwsh(arg: &str) -> String {
+	if is_plain(arg) {
+		arg.to_string()
+	} else {
+		format!("'{}'", arg.replace('\'', "''"))
+	}
+}
+
+/// Nushell single-quoted strings can't contain a single quote, so use double quotes, in which
+/// backslash escapes.
+fn quote_nu(arg: &str) -> String {
+	if is_plain(arg) {
+		arg.to_string()
+	} else {
+		format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+	}
+}
+
+/// The command running `exec_args` in `path` with `interpreter`, see `command_line`. Stdin is
+/// closed to prevent interactive prompts and pagers, and output piped, which also keeps pagers
+/// from detecting a TTY.
+pub(crate) fn shell_command(
+	path: &Path,
+	exec_args: &[String],
+	env: &BTreeMap<String, String>,
+	interpreter: Interpreter,
+) -> Command {
+	let line = command_line(interpreter, exec_args);
+	let (program, args) = line.split_first().map_or(("", &[][..]), |(program, args)| (program.as_str(), args));
+
+	let mut command = Command::new(program);
+	command
+		.args(args)
+		.current_dir(path)
+		.envs(env)
+		.stdin(Stdio::null())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped());
+	command
+}
+
+pub fn repo_exec(
+	path: &Path,
+	exec_args: &[String],
+	env: &BTreeMap<String, String>,
+	options: &ExecOptions,
+) -> Result<RunStatus, Error> {
+	repo_exec_streaming(path, exec_args, env, options).map(|finished| finished.status)
+}
+
+/// Runs like `repo_exec`, printing the output as it comes, and also keeps it for logs and
+/// reports.
+fn repo_exec_streaming(
+	path: &Path,
+	exec_args: &[String],
+	env: &BTreeMap<String, String>,
+	options: &ExecOptions,
+) -> Result<Finished, Error> {
+	println!();
+	println!("🏢 {}> {}", path.display(), format_args_for_display(exec_args));
+
+	let mut command = shell_command(path, exec_args, env, options.interpreter);
+	let mut child_process: Child = spawn(&mut command, options.timeout)?;
+
 	// Stream stdout and stderr in real-time using threads
 	let stdout = child_process
 		.stdout
@@ -227,111 +618,78 @@ fn repo_exec(path: &Path, exec_args: &[String]) -> Result<ExitStatus, Error> {
 
 	let stdout_thread = thread::spawn(move || {
 		let reader = BufReader::new(stdout);
+		let mut text = String::new();
 		for line in reader.lines().map_while(Result::ok) {
 			println!("{}", line);
+			text.push_str(&line);
+			text.push('\n');
 		}
+		text
 	});
 
 	let stderr_thread = thread::spawn(move || {
 		let reader = BufReader::new(stderr);
+		let mut text = String::new();
 		for line in reader.lines().map_while(Result::ok) {
 			eprintln!("{}", line);
+			text.push_str(&line);
+			text.push('\n');
 		}
+		text
 	});
 
-	let exit_code = child_process.wait()?;
+	let status = wait(&mut child_process, options.timeout)?;
 
 	// Wait for output threads to finish
-	let _ = stdout_thread.join();
-	let _ = stderr_thread.join();
+	let stdout = stdout_thread.join().unwrap_or_default();
+	let stderr = stderr_thread.join().unwrap_or_default();
 
-	if !exit_code.success() {
-		eprintln!(
-			"Command exited with code {}",
-			exit_code.code().expect("exit code missing")
-		);
+	if !status.success() {
+		eprintln!("{}", describe_failure(status));
 	}
-	Ok(exit_code)
+	Ok(Finished { status, stdout, stderr, text: None })
 }
 
-fn repo_exec_oneline(path: &Path, exec_args: &[String]) -> Result<(Option<String>, bool), Error> {
-	// If single argument, pass directly to shell for interpretation (supports pipes, etc.)
-	// If multiple arguments, pass via positional parameters to avoid quoting issues
-	#[cfg(unix)]
-	let mut child_process: Child = if exec_args.len() == 1 {
-		Command::new("sh")
-			.arg("-c")
-			.arg(&exec_args[0]) // Single arg passed directly for shell interpretation
-			.current_dir(path)
-			.stdin(Stdio::null())
-			.stdout(Stdio::piped())
-			.stderr(Stdio::piped())
-			.spawn()?
-	} else {
-		Command::new("sh")
-			.arg("-c")
-			.arg(r#""$@""#) // Execute all positional parameters
-			.arg("--") // $0 placeholder (ignored)
-			.args(exec_args) // These become $1, $2, $3, etc.
-			.current_dir(path)
-			.stdin(Stdio::null())
-			.stdout(Stdio::piped())
-			.stderr(Stdio::piped())
-			.spawn()?
-	};
+/// Runs like `repo_exec` but returns the header and output as one piece instead of streaming
+/// it, so the output of repos run in parallel doesn't interleave.
+fn repo_exec_buffered(
+	path: &Path,
+	exec_args: &[String],
+	env: &BTreeMap<String, String>,
+	options: &ExecOptions,
+) -> Result<Finished, Error> {
+	let mut command = shell_command(path, exec_args, env, options.interpreter);
+	let (stdout, stderr, status) = run_collecting(&mut command, options.timeout)?;
 
-	#[cfg(windows)]
-	let mut child_process: Child = if exec_args.len() == 1 {
-		Command::new("cmd")
-			.arg("/C")
-			.arg(&exec_args[0]) // Single arg passed directly for shell interpretation
-			.current_dir(path)
-			.stdin(Stdio::null())
-			.stdout(Stdio::piped())
-			.stderr(Stdio::piped())
-			.spawn()?
-	} else {
-		// Windows cmd doesn't have an equivalent to sh -c "$@"
-		// We need to join args with proper quoting
-		let command_string = exec_args
-			.iter()
-			.map(|arg| {
-				// Quote if contains spaces or special chars
-				if arg.contains(' ') || arg.contains('"') || arg.contains('&') || arg.contains('|')
-				{
-					format!("\"{}\"", arg.replace('"', "\"\""))
-				} else {
-					arg.clone()
-				}
-			})
-			.collect::<Vec<_>>()
-			.join(" ");
-		Command::new("cmd")
-			.arg("/C")
-			.arg(command_string)
-			.current_dir(path)
-			.stdin(Stdio::null())
-			.stdout(Stdio::piped())
-			.stderr(Stdio::piped())
-			.spawn()?
-	};
+	let mut text = format!("\n🏢 {}> {}\n", path.display(), format_args_for_display(exec_args));
+	text.push_str(&stdout);
+	text.push_str(&stderr);
 
-	let mut stdout = String::new();
-	if let Some(mut stdout_pipe) = child_process.stdout.take() {
-		stdout_pipe.read_to_string(&mut stdout)?;
+	if !status.success() {
+		text.push_str(&format!("{}\n", describe_failure(status)));
 	}
 
-	let mut stderr = String::new();
-	if let Some(mut stderr_pipe) = child_process.stderr.take() {
-		stderr_pipe.read_to_string(&mut stderr)?;
-	}
+	Ok(Finished { status, stdout, stderr, text: Some(text) })
+}
 
-	let exit_code = child_process.wait()?;
-	let success = exit_code.success();
+/// Runs the command and returns its output flattened to a line after the path.
+fn repo_exec_oneline(
+	path: &Path,
+	exec_args: &[String],
+	env: &BTreeMap<String, String>,
+	options: &ExecOptions,
+) -> Result<Finished, Error> {
+	let mut command = shell_command(path, exec_args, env, options.interpreter);
+	let (stdout, stderr, status) = run_collecting(&mut command, options.timeout)?;
+	let success = status.success();
 
 	// Flatten multi-line output to single line by replacing newlines with spaces
 	let stdout_clean = stdout.trim().replace('\n', " ");
-	let stderr_clean = stderr.trim().replace('\n', " ");
+	let mut stderr_clean = stderr.trim().replace('\n', " ");
+
+	if let RunStatus::TimedOut(_) = status {
+		stderr_clean = [stderr_clean, describe_failure(status)].join(" ").trim().to_string();
+	}
 
 	// Combine stdout and stderr, with stderr included when command fails
 	let output = if !success && !stderr_clean.is_empty() {
@@ -346,11 +704,272 @@ fn repo_exec_oneline(path: &Path, exec_args: &[String]) -> Result<(Option<String
 		String::new()
 	};
 
-	if output.is_empty() {
-		Ok((None, success))
-	} else {
-		Ok((Some(output), success))
+	let text = format!("{}\t{}", path.display(), output);
+	Ok(Finished { status, stdout, stderr, text: Some(text) })
+}
+
+fn describe_failure(status: RunStatus) -> String {
+	match status {
+		RunStatus::Exited(exit_status) => match exit_status.code() {
+			Some(code) => format!("Command exited with code {code}"),
+			None => format!("Command ended by {exit_status}"),
+		},
+		RunStatus::TimedOut(timeout) => format!("Command timed out after {timeout:?}, killed"),
+	}
+}
+
+/// Spawns `command`. With a timeout, on Unix in a process group of its own, so a timeout can
+/// kill what the command started as well. Only then, because the group also keeps Ctrl-C in
+/// the terminal from reaching the command, which `forward_signal` makes up for. On Windows the
+/// command goes into a job object instead, which its children join.
+fn spawn(command: &mut Command, timeout: Option<Duration>) -> Result<Child, Error> {
+	#[cfg(unix)]
+	if timeout.is_some() {
+		use std::os::unix::process::CommandExt;
+		command.process_group(0);
+
+		let child_process = command.spawn()?;
+		register_group(child_process.id() as i32);
+		return Ok(child_process);
+	}
+
+	#[cfg(windows)]
+	if timeout.is_some() {
+		let mut child_process = command.spawn()?;
+
+		if let Err(error) = job::assign(&child_process) {
+			let _ = child_process.kill();
+			let _ = child_process.wait();
+			return Err(error);
+		}
+		return Ok(child_process);
+	}
+
+	command.spawn()
+}
+
+/// Job objects of the commands running with a timeout, by process id. Closing a job kills what
+/// is still running in it, so nothing a command started outlives it or vaquera.
+#[cfg(windows)]
+mod job {
+	use std::collections::BTreeMap;
+	use std::io::Error;
+	use std::os::windows::io::AsRawHandle;
+	use std::process::Child;
+	use std::sync::Mutex;
+
+	use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+	use windows_sys::Win32::System::JobObjects::{
+		AssignProcessToJobObject, CreateJobObjectW, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+		JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JobObjectExtendedLimitInformation, SetInformationJobObject,
+		TerminateJobObject,
+	};
+
+	// Handles as usize, as raw pointers can't be shared between threads
+	static JOBS: Mutex<BTreeMap<u32, usize>> = Mutex::new(BTreeMap::new());
+
+	/// Puts `child_process` into a new job object that kills its processes once closed.
+	pub(super) fn assign(child_process: &Child) -> Result<(), Error> {
+		// SAFETY: null attributes and name make an anonymous job with default security
+		let job = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+		if job.is_null() {
+			return Err(Error::last_os_error());
+		}
+
+		// SAFETY: the information is a zeroed plain C struct, with the size passed alongside
+		let assigned = unsafe {
+			let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+			info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+
+			SetInformationJobObject(
+				job,
+				JobObjectExtendedLimitInformation,
+				&info as *const _ as *const _,
+				size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+			) != 0 && AssignProcessToJobObject(job, child_process.as_raw_handle() as HANDLE) != 0
+		};
+
+		if !assigned {
+			let error = Error::last_os_error();
+			// SAFETY: `job` is a handle made above and not stored anywhere
+			unsafe {
+				CloseHandle(job);
+			}
+			return Err(error);
+		}
+
+		JOBS.lock().expect("jobs lock poisoned").insert(child_process.id(), job as usize);
+		Ok(())
+	}
+
+	/// Kills every process in the job of process `id`, and closes the job.
+	pub(super) fn terminate(id: u32) {
+		if let Some(job) = JOBS.lock().expect("jobs lock poisoned").remove(&id) {
+			// SAFETY: the handle came from `assign` and was removed from `JOBS`, so is closed once
+			unsafe {
+				TerminateJobObject(job as HANDLE, 1);
+				CloseHandle(job as HANDLE);
+			}
+		}
+	}
+
+	/// Closes the job of process `id`, which kills what is left of it.
+	pub(super) fn close(id: u32) {
+		if let Some(job) = JOBS.lock().expect("jobs lock poisoned").remove(&id) {
+			// SAFETY: the handle came from `assign` and was removed from `JOBS`, so is closed once
+			unsafe {
+				CloseHandle(job as HANDLE);
+			}
+		}
+	}
+}
+
+/// Process groups of the commands running with a timeout, for `forward_signal`. Fixed slots
+/// rather than a collection behind a lock, which a signal handler must not take; 0 is free.
+#[cfg(unix)]
+static LIVE_GROUPS: [AtomicI32; 256] = [const { AtomicI32::new(0) }; 256];
+
+#[cfg(unix)]
+static FORWARD_SIGNALS: Once = Once::new();
+
+/// Remembers process group `group` until `unregister_group`, installing the handler that
+/// forwards SIGINT and SIGTERM to it on first use.
+#[cfg(unix)]
+fn register_group(group: i32) {
+	FORWARD_SIGNALS.call_once(|| {
+		let handler = forward_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+
+		// SAFETY: the handler only makes async-signal-safe calls
+		unsafe {
+			libc::signal(libc::SIGINT, handler);
+			libc::signal(libc::SIGTERM, handler);
+		}
+	});
+
+	let registered = LIVE_GROUPS.iter()
+		.any(|slot| slot.compare_exchange(0, group, Ordering::SeqCst, Ordering::SeqCst).is_ok());
+
+	if !registered {
+		eprintln!("Warning: Too many commands running, Ctrl-C won't reach process group {group}");
+	}
+}
+
+#[cfg(unix)]
+fn unregister_group(group: i32) {
+	for slot in &LIVE_GROUPS {
+		let _ = slot.compare_exchange(group, 0, Ordering::SeqCst, Ordering::SeqCst);
+	}
+}
+
+/// Sends `signal` to the live process groups, which don't get it from the terminal, then ends
+/// vaquera with it as if there were no handler, so no command is left running unnoticed.
+#[cfg(unix)]
+extern "C" fn forward_signal(signal: libc::c_int) {
+	for slot in &LIVE_GROUPS {
+		let group = slot.load(Ordering::SeqCst);
+
+		if group != 0 {
+			// SAFETY: kill only sends a signal; the negative pid addresses the group
+			unsafe {
+				libc::kill(-group, signal);
+			}
+		}
 	}
+
+	// SAFETY: signal and raise are async-signal-safe
+	unsafe {
+		libc::signal(signal, libc::SIG_DFL);
+		libc::raise(signal);
+	}
+}
+
+/// Runs `command` to its end, or its timeout, and returns its stdout and stderr.
+pub(crate) fn run_collecting(command: &mut Command, timeout: Option<Duration>) -> Result<(String, String, RunStatus), Error> {
+	let mut child_process = spawn(command, timeout)?;
+
+	// Read both pipes while waiting, so a command filling one pipe can't block, nor the
+	// timeout wait for the output to end
+	let stdout_thread = read_in_thread(child_process.stdout.take());
+	let stderr_thread = read_in_thread(child_process.stderr.take());
+
+	let status = wait(&mut child_process, timeout)?;
+
+	let stdout = stdout_thread.join().unwrap_or_default();
+	let stderr = stderr_thread.join().unwrap_or_default();
+
+	Ok((stdout, stderr, status))
+}
+
+fn read_in_thread<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<String> {
+	thread::spawn(move || {
+		let mut bytes = Vec::new();
+		if let Some(mut pipe) = pipe {
+			let _ = pipe.read_to_end(&mut bytes);
+		}
+		String::from_utf8_lossy(&bytes).to_string()
+	})
+}
+
+/// Waits for `child_process` to exit, killing it and its process group once `timeout` passed.
+fn wait(child_process: &mut Child, timeout: Option<Duration>) -> Result<RunStatus, Error> {
+	let Some(timeout) = timeout else {
+		return child_process.wait().map(RunStatus::Exited);
+	};
+
+	let deadline = Instant::now() + timeout;
+
+	loop {
+		if let Some(status) = child_process.try_wait()? {
+			#[cfg(unix)]
+			unregister_group(child_process.id() as i32);
+
+			#[cfg(windows)]
+			job::close(child_process.id());
+
+			return Ok(RunStatus::Exited(status));
+		}
+
+		if Instant::now() >= deadline {
+			#[cfg(unix)]
+			// SAFETY: kill only sends a signal; the negative pid addresses the group made by `spawn`
+			unsafe {
+				libc::kill(-(child_process.id() as libc::pid_t), libc::SIGKILL);
+			}
+
+			#[cfg(unix)]
+			unregister_group(child_process.id() as i32);
+
+			#[cfg(windows)]
+			job::terminate(child_process.id());
+
+			// Already gone, the group or job included it
+			let _ = child_process.kill();
+			child_process.wait()?;
+
+			return Ok(RunStatus::TimedOut(timeout));
+		}
+
+		thread::sleep(Duration::from_millis(20));
+	}
+}
+
+/// Parses durations such as `30s`, `500ms`, `5m` or `1h`; a plain number is seconds.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+	let invalid = || format!("Invalid duration '{text}', expected e.g. 30s, 500ms, 5m or 1h");
+	let trimmed = text.trim();
+	let split = trimmed.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(trimmed.len());
+	let (amount, unit) = trimmed.split_at(split);
+	let amount: f64 = amount.parse().map_err(|_| invalid())?;
+
+	let seconds = match unit.trim() {
+		"ms" => amount / 1000.0,
+		"" | "s" | "sec" => amount,
+		"m" | "min" => amount * 60.0,
+		"h" => amount * 3600.0,
+		_ => return Err(invalid()),
+	};
+
+	Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
 }
 
 #[cfg(test)]
@@ -440,4 +1059,66 @@ mod tests {
 			"grep --exclude='*.tmp files'"
 		);
 	}
+
+	#[test]
+	fn test_parse_duration() {
+		assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+		assert_eq!(parse_duration("45"), Ok(Duration::from_secs(45)));
+		assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+		assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+		assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+		assert!(parse_duration("soon").is_err());
+		assert!(parse_duration("5 days").is_err());
+	}
+
+	fn args(args: &[&str]) -> Vec<String> {
+		args.iter().map(|arg| arg.to_string()).collect()
+	}
+
+	#[test]
+	fn test_command_line_single_arg_is_interpreted() {
+		assert_eq!(command_line(Interpreter::Bash, &args(&["ls | wc -l"])), args(&["bash", "-c", "ls | wc -l"]));
+		assert_eq!(command_line(Interpreter::Nu, &args(&["ls | length"])), args(&["nu", "-c", "ls | length"]));
+		assert_eq!(
+			command_line(Interpreter::Pwsh, &args(&["Get-ChildItem | Measure-Object"])),
+			args(&["pwsh", "-NoProfile", "-Command", "Get-ChildItem | Measure-Object"])
+		);
+	}
+
+	#[test]
+	fn test_command_line_posix_passes_args_untouched() {
+		assert_eq!(
+			command_line(Interpreter::Zsh, &args(&["git", "commit", "-m", "Don't $panic"])),
+			args(&["zsh", "-c", "\"$@\"", "--", "git", "commit", "-m", "Don't $panic"])
+		);
+	}
+
+	#[test]
+	fn test_command_line_direct_passes_args_untouched() {
+		assert_eq!(command_line(Interpreter::Direct, &args(&["echo", "a b", "$HOME"])), args(&["echo", "a b", "$HOME"]));
+		assert_eq!(command_line(Interpreter::Direct, &args(&["ls | wc -l"])), args(&["ls | wc -l"]));
+	}
+
+	#[test]
+	fn test_command_line_pwsh_quoting() {
+		assert_eq!(
+			command_line(Interpreter::Pwsh, &args(&["git", "commit", "-m", "Don't panic", "--author=A B", ""])),
+			args(&["pwsh", "-NoProfile", "-Command", "& git commit -m 'Don''t panic' '--author=A B' ''"])
+		);
+	}
+
+	#[test]
+	fn test_command_line_nu_quoting() {
+		assert_eq!(
+			command_line(Interpreter::Nu, &args(&["echo", "say \"hi\"", "C:\\temp", "it's"])),
+			args(&["nu", "-c", "^echo \"say \\\"hi\\\"\" \"C:\\\\temp\" \"it's\""])
+		);
+	}
+
+	#[test]
+	fn test_quote_cmd() {
+		assert_eq!(quote_cmd("status"), "status");
+		assert_eq!(quote_cmd("a & b"), "\"a & b\"");
+		assert_eq!(quote_cmd("say \"hi\""), "\"say \"\"hi\"\"\"");
+	}
 }
@@ -0,0 +1,50 @@
+use crate::vaq_types::VaqUrlBuf;
+
+use bstr::ByteSlice;
+
+/// Remote name of the canonical repo a fork was made from.
+pub const UPSTREAM: &str = "upstream";
+
+/// The URL of `owner`'s fork of the repo at `url`, made by replacing the path segment before
+/// the repo name (the owner on GitHub-like hosts) with `owner`. `None` if the path has no
+/// owner segment.
+pub fn fork_url(url: &VaqUrlBuf, owner: &str) -> Option<VaqUrlBuf> {
+	let path = url.url.path.to_str().ok()?;
+	let (namespace, name) = path.trim_end_matches('/').rsplit_once('/')?;
+
+	let (prefix, current_owner) = match namespace.rsplit_once('/') {
+		Some((prefix, current_owner)) => (format!("{prefix}/"), current_owner),
+		None => (String::new(), namespace),
+	};
+
+	if current_owner.is_empty() || name.is_empty() {
+		return None;
+	}
+
+	let mut fork = url.url.clone();
+	fork.path = format!("{prefix}{owner}/{name}").into();
+
+	Some(VaqUrlBuf::from(fork))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn fork(url: &str, owner: &str) -> Option<String> {
+		let url = VaqUrlBuf::try_from(url).expect("valid url");
+		fork_url(&url, owner).map(|url| url.to_string())
+	}
+
+	#[test]
+	fn swap_owner() {
+		assert_eq!(Some("git@github.com:me/api.git".to_string()), fork("git@github.com:team/api.git", "me"));
+		assert_eq!(Some("https://github.com/me/api".to_string()), fork("https://github.com/team/api", "me"));
+		assert_eq!(Some("/srv/git/me/api.git".to_string()), fork("/srv/git/team/api.git", "me"));
+	}
+
+	#[test]
+	fn no_owner_segment() {
+		assert_eq!(None, fork("https://example.org/api.git", "me"));
+	}
+}
@@ -1,7 +1,13 @@
 use crate::vaq_types::{VaqUrl, VaqUrlBuf, VaqUrlBufError};
+use crate::commit_log::{LogQuery, VaqCommit};
 use crate::remotes::{VaqRemote, VaqRemotes};
 
-use git2::{Error as Git2Error, Remote, Repository};
+use git2::build::CheckoutBuilder;
+use git2::{
+	ApplyLocation, BranchType, Commit, Cred, Diff, Error as Git2Error, FetchOptions, IndexAddOption, PushOptions,
+	Remote, RemoteCallbacks, Repository, Sort, StatusOptions,
+};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 
@@ -10,6 +16,49 @@ pub trait Git {
 	fn read_all_remotes(&self, path: &Path) -> Result<VaqRemotes, VaqError>;
 	fn add_remote(&self, path: &Path, remote_name: &str, url: &VaqUrl);
 	fn clone(&self, path: &Path, url: &VaqUrl) -> Result<(), VaqError>;
+	fn current_branch(&self, path: &Path) -> Result<String, GitError>;
+	fn branch_exists(&self, path: &Path, branch_name: &str) -> Result<bool, GitError>;
+	fn create_branch(&self, path: &Path, branch_name: &str) -> Result<(), GitError>;
+	fn checkout_branch(&self, path: &Path, branch_name: &str) -> Result<(), GitError>;
+	fn delete_branch(&self, path: &Path, branch_name: &str) -> Result<(), GitError>;
+	fn apply_patch(&self, path: &Path, patch: &[u8]) -> Result<(), GitError>;
+	/// Resets the index and working tree to HEAD and removes untracked files.
+	fn discard_changes(&self, path: &Path) -> Result<(), GitError>;
+	/// Stages every change in the working tree and commits it on top of HEAD.
+	/// Returns `false` (and commits nothing) when the working tree has no changes.
+	fn commit_all(&self, path: &Path, message: &str) -> Result<bool, GitError>;
+	fn push(&self, path: &Path, remote_name: &str, refspec: &str) -> Result<(), GitError>;
+	/// Fetches the remote the current branch tracks, or `origin` if it tracks none.
+	fn fetch(&self, path: &Path) -> Result<(), GitError>;
+	/// Fetches and fast-forwards the current branch to its upstream. A branch that has diverged
+	/// from its upstream is left alone.
+	fn pull(&self, path: &Path) -> Result<(), GitError>;
+	fn log(&self, path: &Path, query: &LogQuery) -> Result<Vec<VaqCommit>, GitError>;
+	fn status(&self, path: &Path) -> Result<VaqRepoStatus, GitError>;
+	/// Creates an annotated tag pointing at HEAD.
+	fn create_tag(&self, path: &Path, tag_name: &str, message: &str) -> Result<(), GitError>;
+	fn delete_tag(&self, path: &Path, tag_name: &str) -> Result<(), GitError>;
+	fn remove_remote(&self, path: &Path, remote_name: &str) -> Result<(), GitError>;
+	fn rename_remote(&self, path: &Path, remote_name: &str, new_name: &str) -> Result<(), GitError>;
+	/// Makes local `branch_name` track `upstream`, a remote branch such as `upstream/main`.
+	fn set_branch_upstream(&self, path: &Path, branch_name: &str, upstream: &str) -> Result<(), GitError>;
+	fn set_remote_url(&self, path: &Path, remote_name: &str, url: &str) -> Result<(), GitError>;
+	/// Sets a push URL separate from the fetch URL; `None` makes the remote push to its fetch URL again.
+	fn set_remote_push_url(&self, path: &Path, remote_name: &str, url: Option<&str>) -> Result<(), GitError>;
+	/// Separate push URLs by remote name. Remotes pushing to their fetch URL are omitted.
+	fn read_push_urls(&self, path: &Path) -> Result<BTreeMap<String, String>, GitError>;
+}
+
+/// Working tree and upstream state of a repo. Ahead/behind counts are relative to the
+/// upstream as of the last fetch; nothing is fetched to compute them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VaqRepoStatus {
+	/// `None` when HEAD is detached or unborn.
+	pub branch: Option<String>,
+	pub upstream: Option<String>,
+	pub dirty: bool,
+	pub ahead: usize,
+	pub behind: usize,
 }
 
 pub struct GitImpl {}
@@ -28,6 +77,21 @@ pub enum GitError {
 
 	#[error("Invalid remote URL for '{0}': {1}")]
 	InvalidRemoteUrl(String, String, VaqUrlBufError),
+
+	#[error("HEAD is detached in {}", .0.display())]
+	DetachedHead(PathBuf),
+
+	#[error("Git operation failed in {}: {1}", .0.display())]
+	Operation(PathBuf, Git2Error),
+
+	#[error("Revision '{0}' not found")]
+	InvalidRevision(String, Git2Error),
+
+	#[error("Branch '{1}' in {} tracks no upstream", .0.display())]
+	NoUpstream(PathBuf, String),
+
+	#[error("Branch '{1}' in {} has diverged from its upstream and cannot be fast-forwarded", .0.display())]
+	NotFastForward(PathBuf, String),
 }
 
 impl Git for GitImpl {
@@ -111,4 +175,390 @@ impl Git for GitImpl {
 
 		Ok(())
 	}
+
+	fn current_branch(&self, path: &Path) -> Result<String, GitError> {
+		let repository = open(path)?;
+		let head = repository.head().map_err(|e| GitError::Operation(path.to_owned(), e))?;
+
+		if !head.is_branch() {
+			return Err(GitError::DetachedHead(path.to_owned()));
+		}
+
+		head.shorthand()
+			.map(|name| name.to_string())
+			.ok_or_else(|| GitError::DetachedHead(path.to_owned()))
+	}
+
+	fn branch_exists(&self, path: &Path, branch_name: &str) -> Result<bool, GitError> {
+		let repository = open(path)?;
+
+		match repository.find_branch(branch_name, BranchType::Local) {
+			Ok(_) => Ok(true),
+			Err(error) if error.code() == git2::ErrorCode::NotFound => Ok(false),
+			Err(error) => Err(GitError::Operation(path.to_owned(), error)),
+		}
+	}
+
+	fn create_branch(&self, path: &Path, branch_name: &str) -> Result<(), GitError> {
+		let repository = open(path)?;
+		let op_error = |e| GitError::Operation(path.to_owned(), e);
+
+		let head_commit = repository.head()
+			.and_then(|head| head.peel_to_commit())
+			.map_err(op_error)?;
+
+		repository.branch(branch_name, &head_commit, false).map_err(op_error)?;
+
+		self.checkout_branch(path, branch_name)
+	}
+
+	fn checkout_branch(&self, path: &Path, branch_name: &str) -> Result<(), GitError> {
+		let repository = open(path)?;
+		let op_error = |e| GitError::Operation(path.to_owned(), e);
+
+		repository.set_head(format!("refs/heads/{branch_name}").as_str()).map_err(op_error)?;
+		repository.checkout_head(Some(CheckoutBuilder::new().safe())).map_err(op_error)?;
+
+		Ok(())
+	}
+
+	fn delete_branch(&self, path: &Path, branch_name: &str) -> Result<(), GitError> {
+		let repository = open(path)?;
+
+		repository.find_branch(branch_name, BranchType::Local)
+			.and_then(|mut branch| branch.delete())
+			.map_err(|e| GitError::Operation(path.to_owned(), e))
+	}
+
+	fn apply_patch(&self, path: &Path, patch: &[u8]) -> Result<(), GitError> {
+		let repository = open(path)?;
+		let op_error = |e| GitError::Operation(path.to_owned(), e);
+
+		let diff = Diff::from_buffer(patch).map_err(op_error)?;
+		repository.apply(&diff, ApplyLocation::WorkDir, None).map_err(op_error)
+	}
+
+	fn discard_changes(&self, path: &Path) -> Result<(), GitError> {
+		let repository = open(path)?;
+		let op_error = |e| GitError::Operation(path.to_owned(), e);
+
+		let head = repository.head()
+			.and_then(|head| head.peel(git2::ObjectType::Commit))
+			.map_err(op_error)?;
+
+		repository.reset(&head, git2::ResetType::Hard, None).map_err(op_error)?;
+		repository.checkout_head(Some(CheckoutBuilder::new().force().remove_untracked(true)))
+			.map_err(op_error)
+	}
+
+	fn commit_all(&self, path: &Path, message: &str) -> Result<bool, GitError> {
+		let repository = open(path)?;
+		let op_error = |e| GitError::Operation(path.to_owned(), e);
+
+		let mut index = repository.index().map_err(op_error)?;
+		index.add_all(["*"].iter(), IndexAddOption::DEFAULT, None).map_err(op_error)?;
+		index.update_all(["*"].iter(), None).map_err(op_error)?;
+		index.write().map_err(op_error)?;
+
+		let tree_id = index.write_tree().map_err(op_error)?;
+		let parent = repository.head()
+			.and_then(|head| head.peel_to_commit())
+			.map_err(op_error)?;
+
+		if parent.tree_id() == tree_id {
+			return Ok(false);
+		}
+
+		let tree = repository.find_tree(tree_id).map_err(op_error)?;
+		let signature = repository.signature().map_err(op_error)?;
+
+		repository.commit(Some("HEAD"), &signature, &signature, message, &tree, &[&parent])
+			.map_err(op_error)?;
+
+		Ok(true)
+	}
+
+	fn push(&self, path: &Path, remote_name: &str, refspec: &str) -> Result<(), GitError> {
+		let repository = open(path)?;
+
+		let mut remote = repository.find_remote(remote_name)
+			.map_err(|e| GitError::InvalidRemoteName(remote_name.to_owned(), e))?;
+
+		let mut push_options = PushOptions::new();
+		push_options.remote_callbacks(remote_callbacks());
+
+		remote.push(&[refspec], Some(&mut push_options))
+			.map_err(|e| GitError::Operation(path.to_owned(), e))
+	}
+
+	fn fetch(&self, path: &Path) -> Result<(), GitError> {
+		let repository = open(path)?;
+		let remote_name = upstream_remote(&repository).unwrap_or_else(|| "origin".to_string());
+
+		fetch_remote(&repository, path, &remote_name)
+	}
+
+	fn pull(&self, path: &Path) -> Result<(), GitError> {
+		let repository = open(path)?;
+		let op_error = |e| GitError::Operation(path.to_owned(), e);
+
+		let mut head = repository.head().map_err(op_error)?;
+		if !head.is_branch() {
+			return Err(GitError::DetachedHead(path.to_owned()));
+		}
+		let branch_name = head.shorthand().unwrap_or_default().to_string();
+
+		let remote_name = upstream_remote(&repository)
+			.ok_or_else(|| GitError::NoUpstream(path.to_owned(), branch_name.clone()))?;
+		fetch_remote(&repository, path, &remote_name)?;
+
+		let upstream = repository.find_branch(&branch_name, BranchType::Local)
+			.and_then(|branch| branch.upstream())
+			.map_err(op_error)?;
+		let upstream_commit = repository.reference_to_annotated_commit(upstream.get()).map_err(op_error)?;
+		let (analysis, _) = repository.merge_analysis(&[&upstream_commit]).map_err(op_error)?;
+
+		if analysis.is_up_to_date() {
+			return Ok(());
+		}
+		if !analysis.is_fast_forward() {
+			return Err(GitError::NotFastForward(path.to_owned(), branch_name));
+		}
+
+		// Safe checkout refuses to overwrite local changes, leaving the branch where it was
+		let target = repository.find_object(upstream_commit.id(), None).map_err(op_error)?;
+		repository.checkout_tree(&target, Some(CheckoutBuilder::new().safe())).map_err(op_error)?;
+		head.set_target(upstream_commit.id(), "pull: fast-forward").map_err(op_error)?;
+
+		Ok(())
+	}
+
+	fn log(&self, path: &Path, query: &LogQuery) -> Result<Vec<VaqCommit>, GitError> {
+		let repository = open(path)?;
+		let op_error = |e| GitError::Operation(path.to_owned(), e);
+
+		if repository.is_empty().map_err(op_error)? {
+			return Ok(Vec::new());
+		}
+
+		let mut revwalk = repository.revwalk().map_err(op_error)?;
+		revwalk.set_sorting(Sort::TIME).map_err(op_error)?;
+
+		match &query.to {
+			Some(to) => revwalk.push(resolve_commit(&repository, to)?.id()).map_err(op_error)?,
+			None => revwalk.push_head().map_err(op_error)?,
+		}
+
+		if let Some(from) = &query.from {
+			revwalk.hide(resolve_commit(&repository, from)?.id()).map_err(op_error)?;
+		}
+
+		let author_pattern = query.author.as_ref().map(|author| author.to_lowercase());
+		let mut commits = Vec::new();
+
+		for oid in revwalk {
+			let commit = repository.find_commit(oid.map_err(op_error)?).map_err(op_error)?;
+
+			if query.since.is_some_and(|since| commit.time().seconds() < since) {
+				continue;
+			}
+
+			let author = commit.author();
+			let author_name = author.name().unwrap_or_default().to_string();
+			let author_email = author.email().unwrap_or_default().to_string();
+
+			if let Some(pattern) = &author_pattern {
+				let matches_author = author_name.to_lowercase().contains(pattern)
+					|| author_email.to_lowercase().contains(pattern);
+
+				if !matches_author {
+					continue;
+				}
+			}
+
+			commits.push(VaqCommit {
+				repo: path.display().to_string(),
+				id: commit.id().to_string(),
+				author_name,
+				author_email,
+				time: commit.time().seconds(),
+				offset_minutes: commit.time().offset_minutes(),
+				summary: commit.summary().unwrap_or_default().to_string(),
+				message: commit.message().unwrap_or_default().to_string(),
+			});
+
+			if query.max_count.is_some_and(|max_count| commits.len() >= max_count) {
+				break;
+			}
+		}
+
+		Ok(commits)
+	}
+
+	fn status(&self, path: &Path) -> Result<VaqRepoStatus, GitError> {
+		let repository = open(path)?;
+		let op_error = |e| GitError::Operation(path.to_owned(), e);
+
+		let mut status_options = StatusOptions::new();
+		status_options.include_untracked(true).include_ignored(false);
+
+		let dirty = !repository.statuses(Some(&mut status_options)).map_err(op_error)?.is_empty();
+
+		let head = repository.head().ok();
+		let branch = head.as_ref()
+			.filter(|head| head.is_branch())
+			.and_then(|head| head.shorthand())
+			.map(|name| name.to_string());
+
+		let mut status = VaqRepoStatus { branch, dirty, ..Default::default() };
+
+		let Some(branch_name) = &status.branch else {
+			return Ok(status);
+		};
+
+		let Ok(upstream) = repository.find_branch(branch_name, BranchType::Local)
+			.and_then(|branch| branch.upstream()) else {
+			return Ok(status);
+		};
+
+		status.upstream = upstream.name().ok().flatten().map(|name| name.to_string());
+
+		let local_oid = head.as_ref().and_then(|head| head.target());
+		let upstream_oid = upstream.get().target();
+
+		if let (Some(local_oid), Some(upstream_oid)) = (local_oid, upstream_oid) {
+			let (ahead, behind) = repository.graph_ahead_behind(local_oid, upstream_oid).map_err(op_error)?;
+			status.ahead = ahead;
+			status.behind = behind;
+		}
+
+		Ok(status)
+	}
+
+	fn create_tag(&self, path: &Path, tag_name: &str, message: &str) -> Result<(), GitError> {
+		let repository = open(path)?;
+		let op_error = |e| GitError::Operation(path.to_owned(), e);
+
+		let head = repository.head()
+			.and_then(|head| head.peel(git2::ObjectType::Commit))
+			.map_err(op_error)?;
+		let signature = repository.signature().map_err(op_error)?;
+
+		repository.tag(tag_name, &head, &signature, message, false).map_err(op_error)?;
+		Ok(())
+	}
+
+	fn delete_tag(&self, path: &Path, tag_name: &str) -> Result<(), GitError> {
+		let repository = open(path)?;
+		repository.tag_delete(tag_name).map_err(|e| GitError::Operation(path.to_owned(), e))
+	}
+
+	fn remove_remote(&self, path: &Path, remote_name: &str) -> Result<(), GitError> {
+		let repository = open(path)?;
+		repository.remote_delete(remote_name)
+			.map_err(|e| GitError::InvalidRemoteName(remote_name.to_owned(), e))
+	}
+
+	fn rename_remote(&self, path: &Path, remote_name: &str, new_name: &str) -> Result<(), GitError> {
+		let repository = open(path)?;
+
+		// Non-default fetch refspecs are left alone by git; they are returned here but not worth failing over
+		repository.remote_rename(remote_name, new_name)
+			.map(|_problems| ())
+			.map_err(|e| GitError::InvalidRemoteName(new_name.to_owned(), e))
+	}
+
+	fn set_branch_upstream(&self, path: &Path, branch_name: &str, upstream: &str) -> Result<(), GitError> {
+		let repository = open(path)?;
+		let mut branch = repository.find_branch(branch_name, BranchType::Local)
+			.map_err(|e| GitError::Operation(path.to_owned(), e))?;
+
+		branch.set_upstream(Some(upstream))
+			.map_err(|e| GitError::Operation(path.to_owned(), e))
+	}
+
+	fn set_remote_url(&self, path: &Path, remote_name: &str, url: &str) -> Result<(), GitError> {
+		let repository = open(path)?;
+		repository.remote_set_url(remote_name, url)
+			.map_err(|e| GitError::Operation(path.to_owned(), e))
+	}
+
+	fn set_remote_push_url(&self, path: &Path, remote_name: &str, url: Option<&str>) -> Result<(), GitError> {
+		let repository = open(path)?;
+		repository.remote_set_pushurl(remote_name, url)
+			.map_err(|e| GitError::Operation(path.to_owned(), e))
+	}
+
+	fn read_push_urls(&self, path: &Path) -> Result<BTreeMap<String, String>, GitError> {
+		let repository = open(path)?;
+		let remote_names = repository.remotes().map_err(|e| GitError::Operation(path.to_owned(), e))?;
+
+		let push_urls = remote_names.iter()
+			.flatten()
+			.filter_map(|remote_name| {
+				let remote = repository.find_remote(remote_name).ok()?;
+				let push_url = remote.pushurl()?;
+				Some((remote_name.to_string(), push_url.to_string()))
+			})
+			.collect();
+
+		Ok(push_urls)
+	}
+}
+
+fn resolve_commit<'r>(repository: &'r Repository, revision: &str) -> Result<Commit<'r>, GitError> {
+	repository.revparse_single(revision)
+		.and_then(|object| object.peel_to_commit())
+		.map_err(|e| GitError::InvalidRevision(revision.to_owned(), e))
+}
+
+fn open(path: &Path) -> Result<Repository, GitError> {
+	Repository::open(path).map_err(|e| GitError::InvalidPath(path.to_owned(), e))
+}
+
+/// The remote the current branch tracks, from `branch.<name>.remote`.
+fn upstream_remote(repository: &Repository) -> Option<String> {
+	let head = repository.head().ok()?;
+	let remote = repository.branch_upstream_remote(head.name()?).ok()?;
+	remote.as_str().map(|name| name.to_string())
+}
+
+/// Fetches with the remote's configured refspecs, like `git fetch <remote>`.
+fn fetch_remote(repository: &Repository, path: &Path, remote_name: &str) -> Result<(), GitError> {
+	let mut remote = repository.find_remote(remote_name)
+		.map_err(|e| GitError::InvalidRemoteName(remote_name.to_owned(), e))?;
+
+	let mut fetch_options = FetchOptions::new();
+	fetch_options.remote_callbacks(remote_callbacks());
+
+	remote.fetch(&[] as &[&str], Some(&mut fetch_options), None)
+		.map_err(|e| GitError::Operation(path.to_owned(), e))
+}
+
+/// Credentials are resolved the same way the git CLI would: ssh-agent for SSH remotes,
+/// configured credential helpers for everything else. Each is offered once: libgit2 asks
+/// again after a rejection, and would otherwise be handed the same credential forever.
+fn remote_callbacks<'a>() -> RemoteCallbacks<'a> {
+	let mut callbacks = RemoteCallbacks::new();
+	let mut attempts = 0;
+
+	callbacks.credentials(move |url, username, allowed| {
+		if allowed.is_username() {
+			return Cred::username(username.unwrap_or("git"));
+		}
+
+		attempts += 1;
+		if attempts > 1 {
+			return Err(Git2Error::from_str("Authentication failed, the credential was rejected"));
+		}
+
+		if allowed.is_ssh_key() {
+			Cred::ssh_key_from_agent(username.unwrap_or("git"))
+		} else {
+			let config = git2::Config::open_default()?;
+			Cred::credential_helper(&config, url, username)
+		}
+	});
+
+	callbacks
 }
@@ -0,0 +1,224 @@
+use crate::exec::exists;
+use crate::repos::VaqRepo;
+
+use std::io::Error;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use serde_derive::Serialize;
+use thiserror::Error;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GrepMode {
+	#[default]
+	Lines,
+	FilesWithMatches,
+	Count,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GrepOptions {
+	pub mode: GrepMode,
+	pub ignore_case: bool,
+	pub fixed_strings: bool,
+}
+
+/// A single hit. Which of `line`/`text`/`count` are set depends on the `GrepMode`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct GrepMatch {
+	pub repo: String,
+	pub path: String,
+
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub line: Option<usize>,
+
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub text: Option<String>,
+
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub count: Option<usize>,
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum GrepError {
+	#[error("Repo folder missing")]
+	MissingFolder,
+
+	#[error("Failed to run git grep. {0}")]
+	Spawn(Error),
+
+	#[error("git grep failed. {0}")]
+	Git(String),
+}
+
+pub struct GrepRepoResult {
+	pub repo: VaqRepo,
+	pub result: Result<Vec<GrepMatch>, GrepError>,
+}
+
+impl GrepMatch {
+	/// Formats the match the way `git grep` would, prefixed with the repo path.
+	pub fn to_line(&self) -> String {
+		let full_path = format!("{}/{}", self.repo, self.path);
+
+		match (self.line, &self.text, self.count) {
+			(Some(line), Some(text), _) => format!("{full_path}:{line}:{text}"),
+			(_, _, Some(count)) => format!("{full_path}:{count}"),
+			_ => full_path,
+		}
+	}
+}
+
+/// Searches the tracked files of every repo in parallel with `git grep`, so each repo's
+/// `.gitignore` is respected. A repo without matches yields an empty result, not an error.
+pub fn grep(pattern: &str, repos: Vec<VaqRepo>, options: &GrepOptions) -> Vec<GrepRepoResult> {
+	thread::scope(|scope| {
+		let handles: Vec<_> = repos
+			.into_iter()
+			.map(|repo| {
+				scope.spawn(move || {
+					let result = repo_grep(&repo, pattern, options);
+					GrepRepoResult { repo, result }
+				})
+			})
+			.collect();
+
+		handles
+			.into_iter()
+			.map(|handle| handle.join().expect("grep thread panicked"))
+			.collect()
+	})
+}
+
+fn repo_grep(repo: &VaqRepo, pattern: &str, options: &GrepOptions) -> Result<Vec<GrepMatch>, GrepError> {
+	if !exists(&repo.path) {
+		return Err(GrepError::MissingFolder);
+	}
+
+	let output = Command::new("git")
+		.args(grep_args(pattern, options))
+		.current_dir(&repo.path)
+		.stdin(Stdio::null())
+		.output()
+		.map_err(GrepError::Spawn)?;
+
+	match output.status.code() {
+		Some(0) => {}
+		// git grep exits with 1 when nothing matched
+		Some(1) if output.stderr.is_empty() => return Ok(Vec::new()),
+		_ => return Err(GrepError::Git(String::from_utf8_lossy(&output.stderr).trim().to_string())),
+	}
+
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	Ok(parse_grep_output(&repo.path, &stdout, options.mode))
+}
+
+fn grep_args(pattern: &str, options: &GrepOptions) -> Vec<String> {
+	let mut args: Vec<String> = vec!["grep", "--null", "-I", "--no-color"]
+		.into_iter()
+		.map(String::from)
+		.collect();
+
+	match options.mode {
+		GrepMode::Lines => args.push("--line-number".to_string()),
+		GrepMode::FilesWithMatches => args.push("--files-with-matches".to_string()),
+		GrepMode::Count => args.push("--count".to_string()),
+	}
+
+	if options.ignore_case {
+		args.push("--ignore-case".to_string());
+	}
+
+	if options.fixed_strings {
+		args.push("--fixed-strings".to_string());
+	}
+
+	args.push("-e".to_string());
+	args.push(pattern.to_string());
+	args
+}
+
+/// Parses `git grep --null` output, where the path is NUL terminated so any character
+/// (including `:`) can appear in file names.
+fn parse_grep_output(repo_path: &Path, stdout: &str, mode: GrepMode) -> Vec<GrepMatch> {
+	let repo = repo_path.display().to_string();
+
+	let new_match = |path: &str| GrepMatch {
+		repo: repo.clone(),
+		path: path.to_string(),
+		line: None,
+		text: None,
+		count: None,
+	};
+
+	match mode {
+		GrepMode::FilesWithMatches => stdout
+			.split('\0')
+			.map(|path| path.trim_start_matches('\n'))
+			.filter(|path| !path.is_empty())
+			.map(new_match)
+			.collect(),
+
+		GrepMode::Count => stdout
+			.lines()
+			.filter_map(|line| line.split_once('\0'))
+			.map(|(path, count)| GrepMatch {
+				count: count.parse().ok(),
+				..new_match(path)
+			})
+			.collect(),
+
+		GrepMode::Lines => stdout
+			.lines()
+			.filter_map(|line| {
+				let mut fields = line.splitn(3, '\0');
+				let path = fields.next()?;
+				let line_number = fields.next()?.parse().ok()?;
+				let text = fields.next().unwrap_or_default();
+
+				Some(GrepMatch {
+					line: Some(line_number),
+					text: Some(text.to_string()),
+					..new_match(path)
+				})
+			})
+			.collect(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_lines() {
+		let stdout = "src/main.rs\u{0}12\u{0}let foo = 1;\nsrc/a:b.rs\u{0}3\u{0}foo: bar\n";
+		let matches = parse_grep_output(Path::new("repo"), stdout, GrepMode::Lines);
+
+		assert_eq!(2, matches.len());
+		assert_eq!("repo/src/main.rs:12:let foo = 1;", matches[0].to_line());
+		assert_eq!("repo/src/a:b.rs:3:foo: bar", matches[1].to_line());
+	}
+
+	#[test]
+	fn parse_files_with_matches() {
+		let stdout = "src/main.rs\u{0}README.md\u{0}";
+		let matches = parse_grep_output(Path::new("repo"), stdout, GrepMode::FilesWithMatches);
+
+		assert_eq!(2, matches.len());
+		assert_eq!("repo/src/main.rs", matches[0].to_line());
+		assert_eq!("repo/README.md", matches[1].to_line());
+	}
+
+	#[test]
+	fn parse_count() {
+		let stdout = "src/main.rs\u{0}4\n";
+		let matches = parse_grep_output(Path::new("repo"), stdout, GrepMode::Count);
+
+		assert_eq!(1, matches.len());
+		assert_eq!(Some(4), matches[0].count);
+		assert_eq!("repo/src/main.rs:4", matches[0].to_line());
+	}
+}
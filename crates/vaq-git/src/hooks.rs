@@ -0,0 +1,85 @@
+use crate::exec::{repo_env, shell_command, Interpreter};
+use crate::repos::VaqRepo;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+
+use derive_more::Display;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Points in vaquera's operations where a hook from the `[hooks]` table runs:
+///
+/// ```toml
+/// [hooks]
+/// post-clone = "npm ci"
+/// pre-exec = "git fetch --quiet"
+/// ```
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookEvent {
+	/// After `clone` cloned a repo and added its remotes.
+	#[display("post-clone")]
+	PostClone,
+
+	/// After a repo was added to the config, by `add` or `clone <url>`.
+	#[display("post-add")]
+	PostAdd,
+
+	/// Before `exec` or `run` runs the command in a repo; if the hook fails, the command is skipped.
+	#[display("pre-exec")]
+	PreExec,
+
+	/// After `exec` or `run` ran the command in a repo, with its exit code in `VAQUERA_EXIT_CODE`
+	/// (1 if it has none, e.g. after a timeout).
+	#[display("post-exec")]
+	PostExec,
+
+	/// After `move` or `relayout` moved a repo, run in its new folder.
+	#[display("post-move")]
+	PostMove,
+
+	/// After `sync` synced the remotes of a repo.
+	#[display("post-sync")]
+	PostSync,
+}
+
+/// Command lines by event, each run by the shell like a single `exec` argument.
+pub type Hooks = BTreeMap<HookEvent, String>;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum HookError {
+	#[error("Failed to start {event} hook in {}. {error}", path.display())]
+	Spawn { event: HookEvent, path: PathBuf, error: io::Error },
+
+	#[error("{event} hook failed in {}: {status}", path.display())]
+	Failed { event: HookEvent, path: PathBuf, status: ExitStatus },
+}
+
+/// Runs the hook configured for `event`, if any, in the repo folder. The hook gets the
+/// `VAQUERA_REPO_*` variables of `exec::repo_env`, `VAQUERA_HOOK` with the event name and `env`.
+/// Its output goes to stderr, to keep the output of the command it accompanies clean.
+pub fn run(hooks: &Hooks, event: HookEvent, repo: &VaqRepo, env: &BTreeMap<String, String>) -> Result<(), HookError> {
+	let Some(command_line) = hooks.get(&event) else {
+		return Ok(());
+	};
+
+	let mut hook_env = repo_env(repo);
+	hook_env.insert("VAQUERA_HOOK".to_string(), event.to_string());
+	hook_env.extend(env.clone());
+
+	let status = shell_command(&repo.path, std::slice::from_ref(command_line), &hook_env, Interpreter::System)
+		.stdout(Stdio::from(io::stderr()))
+		.stderr(Stdio::inherit())
+		.status()
+		.map_err(|error| HookError::Spawn { event, path: repo.path.clone(), error })?;
+
+	if !status.success() {
+		return Err(HookError::Failed { event, path: repo.path.clone(), status });
+	}
+
+	Ok(())
+}
@@ -0,0 +1,177 @@
+use crate::storage::Storage;
+
+use std::io;
+use std::path::PathBuf;
+
+use chrono::{Local, TimeZone, Utc};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// How many changes are kept; older ones are dropped.
+const MAX_ENTRIES: usize = 50;
+
+/// A repo folder moved along with a config change, so undoing it can move the folder back.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FolderMove {
+	pub from: PathBuf,
+	pub to: PathBuf,
+}
+
+/// One change to `.vaquera.toml`, with the whole file before and after it.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct JournalEntry {
+	/// Unix time in seconds.
+	pub time: i64,
+	pub action: String,
+
+	/// Empty when the file didn't exist yet.
+	pub before: String,
+	pub after: String,
+
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub moves: Vec<FolderMove>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Journal {
+	#[serde(default)]
+	entries: Vec<JournalEntry>,
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum JournalError {
+	#[error("History is not recorded")]
+	Disabled,
+
+	#[error("Cannot undo {requested} changes, only {available} recorded")]
+	NothingToUndo { requested: usize, available: usize },
+
+	#[error(".vaquera.toml was changed outside vaquera since the last recorded change; undoing would lose that")]
+	Diverged,
+
+	#[error("Failed to parse history as valid TOML. {0}")]
+	Parse(toml::de::Error),
+
+	#[error("Failed to generate toml for history. {0}")]
+	Serialize(toml::ser::Error),
+
+	#[error("Failed to write history. {0}")]
+	Save(io::Error),
+}
+
+impl JournalEntry {
+	pub fn new(action: &str, before: String, after: String, moves: Vec<FolderMove>) -> Self {
+		JournalEntry {
+			time: Utc::now().timestamp(),
+			action: action.to_string(),
+			before,
+			after,
+			moves,
+		}
+	}
+
+	/// When the change was made, in local time.
+	pub fn date(&self) -> String {
+		Local.timestamp_opt(self.time, 0)
+			.single()
+			.map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+			.unwrap_or_default()
+	}
+}
+
+impl Journal {
+	pub fn load(storage: &dyn Storage) -> Result<Self, JournalError> {
+		if !storage.exists() {
+			return Ok(Journal::default());
+		}
+
+		toml::from_str(&storage.read()).map_err(JournalError::Parse)
+	}
+
+	pub fn save(&self, storage: &dyn Storage) -> Result<(), JournalError> {
+		let journal_toml = toml::to_string(self).map_err(JournalError::Serialize)?;
+		storage.save(journal_toml).map_err(JournalError::Save)
+	}
+
+	pub fn push(&mut self, entry: JournalEntry) {
+		self.entries.push(entry);
+
+		if self.entries.len() > MAX_ENTRIES {
+			self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+		}
+	}
+
+	/// Recorded changes, most recent first.
+	pub fn entries(&self) -> impl Iterator<Item = &JournalEntry> {
+		self.entries.iter().rev()
+	}
+
+	/// Removes the `count` most recent changes and returns them, most recent first.
+	/// `current` is the config as it is now, which must be what the last change left.
+	pub fn pop(&mut self, count: usize, current: &str) -> Result<Vec<JournalEntry>, JournalError> {
+		if count == 0 || count > self.entries.len() {
+			return Err(JournalError::NothingToUndo { requested: count, available: self.entries.len() });
+		}
+
+		if self.entries.last().is_some_and(|last| last.after != current) {
+			return Err(JournalError::Diverged);
+		}
+
+		let mut popped = self.entries.split_off(self.entries.len() - count);
+		popped.reverse();
+		Ok(popped)
+	}
+}
+
+/// Records `entry` at the end of the journal in `storage`.
+pub fn append(storage: &dyn Storage, entry: JournalEntry) -> Result<(), JournalError> {
+	let mut journal = Journal::load(storage)?;
+	journal.push(entry);
+	journal.save(storage)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry(action: &str, before: &str, after: &str) -> JournalEntry {
+		JournalEntry::new(action, before.to_string(), after.to_string(), Vec::new())
+	}
+
+	#[test]
+	fn pop_most_recent_first() {
+		let mut journal = Journal::default();
+		journal.push(entry("add a", "", "a"));
+		journal.push(entry("add b", "a", "ab"));
+		journal.push(entry("remove a", "ab", "b"));
+
+		let popped = journal.pop(2, "b").expect("undo failed");
+
+		assert_eq!(vec!["remove a", "add b"], popped.iter().map(|e| e.action.as_str()).collect::<Vec<_>>());
+		assert_eq!("a", popped.last().expect("popped entries").before);
+		assert_eq!(1, journal.entries().count());
+	}
+
+	#[test]
+	fn pop_refuses_changed_config() {
+		let mut journal = Journal::default();
+		journal.push(entry("add a", "", "a"));
+
+		assert!(matches!(journal.pop(1, "edited by hand"), Err(JournalError::Diverged)));
+		assert!(matches!(journal.pop(2, "a"), Err(JournalError::NothingToUndo { requested: 2, available: 1 })));
+		assert_eq!(1, journal.entries().count());
+	}
+
+	#[test]
+	fn keeps_recent_entries_only() {
+		let mut journal = Journal::default();
+
+		for i in 0..MAX_ENTRIES + 5 {
+			journal.push(entry(&format!("change {i}"), "", ""));
+		}
+
+		assert_eq!(MAX_ENTRIES, journal.entries().count());
+		assert_eq!(Some("change 5"), journal.entries().last().map(|e| e.action.as_str()));
+	}
+}
@@ -1,10 +1,29 @@
 extern crate core;
 
+pub mod batch;
+pub mod changelog;
+pub mod commands;
+pub mod commit_log;
+pub mod dashboard;
+pub mod doctor;
 pub mod exec;
+pub mod fork;
 pub mod git;
+pub mod grep;
+pub mod hooks;
+pub mod journal;
+pub mod lookup;
+pub mod release;
+pub mod relocate;
+pub mod remote_check;
 pub mod remotes;
+pub mod report;
 pub mod repos;
+pub mod rewrite;
+pub mod settings;
 pub mod storage;
+pub mod sync;
 pub mod tag_filter;
+pub mod template;
 pub mod vaq_git;
 pub mod vaq_types;
@@ -0,0 +1,105 @@
+use crate::repos::VaqRepo;
+
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LookupError {
+	#[error("No repo matches '{0}'")]
+	NotFound(String),
+
+	#[error("'{query}' matches several repos: {}", .candidates.join(", "))]
+	Ambiguous { query: String, candidates: Vec<String> },
+}
+
+/// Finds the repo meant by `query`, trying ever looser matches until one finds something:
+/// exact path, exact name, name ignoring case, name prefix, name substring, path substring,
+/// and finally the query's letters appearing in order in the name (`srvapi` → `service-api`).
+/// A looser match is only used if the stricter ones found nothing, and must be unique.
+pub fn find_repo<'r>(repos: &'r [VaqRepo], query: &str) -> Result<&'r VaqRepo, LookupError> {
+	let lowercase = query.to_lowercase();
+	let name = |repo: &VaqRepo| repo.name.to_lowercase();
+	let path = |repo: &VaqRepo| repo.path.to_string_lossy().to_lowercase();
+
+	let tiers: [&dyn Fn(&VaqRepo) -> bool; 7] = [
+		&|repo| repo.path == Path::new(query.trim_end_matches('/')),
+		&|repo| repo.name == query,
+		&|repo| name(repo) == lowercase,
+		&|repo| name(repo).starts_with(&lowercase),
+		&|repo| name(repo).contains(&lowercase),
+		&|repo| path(repo).contains(&lowercase),
+		&|repo| is_subsequence(&lowercase, &name(repo)),
+	];
+
+	for matches in tiers {
+		let found: Vec<&VaqRepo> = repos.iter().filter(|repo| matches(repo)).collect();
+
+		match found.as_slice() {
+			[] => continue,
+			[repo] => return Ok(repo),
+			_ => {
+				return Err(LookupError::Ambiguous {
+					query: query.to_string(),
+					candidates: found.iter().map(|repo| repo.path.display().to_string()).collect(),
+				});
+			}
+		}
+	}
+
+	Err(LookupError::NotFound(query.to_string()))
+}
+
+/// The closest folder at or above `start` containing `state_file`.
+pub fn find_workspace_root(start: &Path, state_file: &str) -> Option<PathBuf> {
+	start
+		.ancestors()
+		.find(|dir| dir.join(state_file).is_file())
+		.map(Path::to_path_buf)
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+	let mut haystack = haystack.chars();
+	needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn repos(paths: &[&str]) -> Vec<VaqRepo> {
+		paths
+			.iter()
+			.map(|path| VaqRepo::new(Path::new(path)).expect("valid repo"))
+			.collect()
+	}
+
+	fn find<'r>(repos: &'r [VaqRepo], query: &str) -> Result<&'r str, LookupError> {
+		find_repo(repos, query).map(|repo| repo.path.to_str().expect("utf8 path"))
+	}
+
+	#[test]
+	fn stricter_match_wins() {
+		let repos = repos(&["libs/api", "services/api-gateway", "services/web"]);
+
+		assert_eq!(Ok("libs/api"), find(&repos, "api"));
+		assert_eq!(Ok("services/api-gateway"), find(&repos, "api-g"));
+		assert_eq!(Ok("services/web"), find(&repos, "WEB"));
+		assert_eq!(Ok("services/api-gateway"), find(&repos, "apgw"));
+	}
+
+	#[test]
+	fn ambiguous_and_missing() {
+		let repos = repos(&["services/api", "services/web"]);
+
+		assert_eq!(
+			Err(LookupError::Ambiguous {
+				query: "services".to_string(),
+				candidates: vec!["services/api".to_string(), "services/web".to_string()],
+			}),
+			find(&repos, "services"),
+		);
+		assert_eq!(Err(LookupError::NotFound("docs".to_string())), find(&repos, "docs"));
+	}
+}
@@ -0,0 +1,142 @@
+use crate::exec::exists;
+use crate::git::Git;
+use crate::repos::VaqRepo;
+
+use std::path::PathBuf;
+
+use log::info;
+use thiserror::Error;
+
+#[derive(Clone, Debug, Default)]
+pub struct ReleaseOptions {
+	/// Tag annotation; defaults to `Release <version>`.
+	pub message: Option<String>,
+	pub dry_run: bool,
+
+	/// Remote to push the tag to once every repo has been tagged.
+	pub push: Option<String>,
+
+	/// Compare with the upstream branches as last fetched, instead of fetching them first.
+	pub no_fetch: bool,
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ReleaseError {
+	#[error("{} repos are not ready for release", .0.len())]
+	NotReady(Vec<(PathBuf, String)>),
+
+	#[error("Failed to tag {}: {error}. Tags already created were removed", path.display())]
+	TagFailed { path: PathBuf, error: String },
+
+	#[error("{} repos failed to push the tag", .0.len())]
+	PushFailed(Vec<(PathBuf, String)>),
+}
+
+/// Tags HEAD of every repo with an annotated `version` tag, all or nothing.
+///
+/// Every repo must be clean and in sync with its upstream, fetched first unless
+/// `ReleaseOptions::no_fetch`, before anything is tagged. If
+/// creating a tag fails, the tags created so far are deleted again. Pushing happens only
+/// after all repos were tagged; a failed push leaves the local tags in place so it can be retried.
+pub fn tag(git: &dyn Git, repos: &[VaqRepo], version: &str, options: &ReleaseOptions) -> Result<(), ReleaseError> {
+	let problems: Vec<(PathBuf, String)> = repos
+		.iter()
+		.filter_map(|repo| check_ready(git, repo, !options.no_fetch).err().map(|problem| (repo.path.clone(), problem)))
+		.collect();
+
+	if !problems.is_empty() {
+		return Err(ReleaseError::NotReady(problems));
+	}
+
+	if options.dry_run {
+		for repo in repos {
+			info!("Would tag {} as {}", repo.path.display(), version);
+		}
+		return Ok(());
+	}
+
+	let message = options.message.clone().unwrap_or_else(|| format!("Release {version}"));
+	let mut tagged: Vec<&VaqRepo> = Vec::new();
+
+	for repo in repos {
+		if let Err(error) = git.create_tag(repo.path.as_path(), version, message.as_str()) {
+			for tagged_repo in tagged {
+				if let Err(rollback_error) = git.delete_tag(tagged_repo.path.as_path(), version) {
+					eprintln!(
+						"Warning: Could not remove tag {} from {}: {}",
+						version,
+						tagged_repo.path.display(),
+						rollback_error
+					);
+				}
+			}
+
+			return Err(ReleaseError::TagFailed {
+				path: repo.path.clone(),
+				error: error.to_string(),
+			});
+		}
+
+		info!("Tagged {} as {}", repo.path.display(), version);
+		tagged.push(repo);
+	}
+
+	let Some(remote_name) = &options.push else {
+		return Ok(());
+	};
+
+	let refspec = format!("refs/tags/{0}:refs/tags/{0}", version);
+	let push_failures: Vec<(PathBuf, String)> = repos
+		.iter()
+		.filter_map(|repo| {
+			match git.push(repo.path.as_path(), remote_name, refspec.as_str()) {
+				Ok(()) => {
+					info!("Pushed {} to {}", version, repo.path.display());
+					None
+				}
+				Err(error) => Some((repo.path.clone(), error.to_string())),
+			}
+		})
+		.collect();
+
+	if push_failures.is_empty() {
+		Ok(())
+	} else {
+		Err(ReleaseError::PushFailed(push_failures))
+	}
+}
+
+fn check_ready(git: &dyn Git, repo: &VaqRepo, fetch: bool) -> Result<(), String> {
+	if !exists(&repo.path) {
+		return Err("repo folder missing".to_string());
+	}
+
+	let status = git.status(repo.path.as_path()).map_err(|e| e.to_string())?;
+
+	if status.branch.is_none() {
+		return Err("HEAD is not on a branch".to_string());
+	}
+
+	if status.dirty {
+		return Err("uncommitted changes".to_string());
+	}
+
+	let Some(upstream) = status.upstream else {
+		return Err("no upstream branch".to_string());
+	};
+
+	let status = if fetch {
+		git.fetch(repo.path.as_path()).map_err(|e| format!("failed to fetch {upstream}: {e}"))?;
+		git.status(repo.path.as_path()).map_err(|e| e.to_string())?
+	} else {
+		status
+	};
+
+	match (status.ahead, status.behind) {
+		(0, 0) => Ok(()),
+		(ahead, 0) => Err(format!("{ahead} commits ahead of {upstream}")),
+		(0, behind) => Err(format!("{behind} commits behind {upstream}")),
+		(ahead, behind) => Err(format!("diverged from {upstream} ({ahead} ahead, {behind} behind)")),
+	}
+}
@@ -0,0 +1,286 @@
+use crate::vaq_git::display_paths;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use derive_more::Display;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum MoveError {
+	#[error("Repo '{0}' not found")]
+	NotFound(String),
+
+	#[error("More than one repo is named '{0}', use its path instead")]
+	Ambiguous(String),
+
+	#[error("Target {} already exists", .0.display())]
+	TargetExists(PathBuf),
+
+	#[error("Failed to move {} to {}. {error}", from.display(), to.display())]
+	Io { from: PathBuf, to: PathBuf, error: io::Error },
+
+	#[error("Copy of {} differs from the original, which was kept", .0.display())]
+	VerifyFailed(PathBuf),
+
+	#[error("{}\n{} repos cannot be moved, nothing moved", describe_collisions(.0), count_collisions(.0))]
+	LayoutCollisions(Vec<LayoutCollision>),
+}
+
+/// Why `relayout` can't move a repo, found before anything is moved.
+#[derive(Debug, Display)]
+pub enum LayoutCollision {
+	#[display("Cannot move {} to {}: same target", display_paths(old_paths), new_path.display())]
+	SameTarget { old_paths: Vec<PathBuf>, new_path: PathBuf },
+
+	#[display("Cannot move {} to {}: target already exists", old_path.display(), new_path.display())]
+	TargetExists { old_path: PathBuf, new_path: PathBuf },
+}
+
+impl LayoutCollision {
+	/// How many repos this keeps from moving.
+	pub fn repo_count(&self) -> usize {
+		match self {
+			LayoutCollision::SameTarget { old_paths, .. } => old_paths.len(),
+			LayoutCollision::TargetExists { .. } => 1,
+		}
+	}
+}
+
+fn describe_collisions(collisions: &[LayoutCollision]) -> String {
+	collisions.iter().map(|collision| collision.to_string()).collect::<Vec<_>>().join("\n")
+}
+
+fn count_collisions(collisions: &[LayoutCollision]) -> usize {
+	collisions.iter().map(LayoutCollision::repo_count).sum()
+}
+
+/// Moves a folder, creating the parents of `to` as needed. Across filesystems, where a rename
+/// is impossible, the folder is copied, the copy compared with the original and only then is
+/// the original deleted. A failed copy is cleaned up and leaves the original untouched.
+pub fn move_dir(from: &Path, to: &Path) -> Result<(), MoveError> {
+	let io_error = |error| MoveError::Io { from: from.to_path_buf(), to: to.to_path_buf(), error };
+
+	if let Some(parent) = to.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+		fs::create_dir_all(parent).map_err(io_error)?;
+	}
+
+	match fs::rename(from, to) {
+		Ok(()) => Ok(()),
+		Err(error) if error.kind() == ErrorKind::CrossesDevices => copy_verify_delete(from, to),
+		Err(error) => Err(io_error(error)),
+	}
+}
+
+fn copy_verify_delete(from: &Path, to: &Path) -> Result<(), MoveError> {
+	let io_error = |error| MoveError::Io { from: from.to_path_buf(), to: to.to_path_buf(), error };
+
+	let copied = copy_dir(from, to)
+		.and_then(|()| verify_copy(from, to))
+		.map_err(io_error)
+		.and_then(|same| if same { Ok(()) } else { Err(MoveError::VerifyFailed(from.to_path_buf())) });
+
+	if let Err(error) = copied {
+		let _ = fs::remove_dir_all(to);
+		return Err(error);
+	}
+
+	fs::remove_dir_all(from).map_err(io_error)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+	fs::create_dir(to)?;
+
+	for entry in fs::read_dir(from)? {
+		let entry = entry?;
+		let file_type = entry.file_type()?;
+		let target = to.join(entry.file_name());
+
+		if file_type.is_dir() {
+			copy_dir(&entry.path(), &target)?;
+		} else if file_type.is_symlink() {
+			copy_symlink(&entry.path(), &target)?;
+		} else {
+			fs::copy(entry.path(), &target)?;
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+	std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+	let target = fs::read_link(from)?;
+
+	if from.is_dir() {
+		std::os::windows::fs::symlink_dir(target, to)
+	} else {
+		std::os::windows::fs::symlink_file(target, to)
+	}
+}
+
+/// Whether the tree at `to` is an exact copy of the one at `from`: the same entries, symlink
+/// targets and file contents.
+fn verify_copy(from: &Path, to: &Path) -> io::Result<bool> {
+	let entries = snapshot(from)?;
+
+	if entries != snapshot(to)? {
+		return Ok(false);
+	}
+
+	for (relative, entry) in &entries {
+		if matches!(entry, Entry::File(_)) && !same_contents(&from.join(relative), &to.join(relative))? {
+			return Ok(false);
+		}
+	}
+
+	Ok(true)
+}
+
+fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+	let mut a = BufReader::new(File::open(a)?);
+	let mut b = BufReader::new(File::open(b)?);
+
+	loop {
+		let a_bytes = a.fill_buf()?;
+		let b_bytes = b.fill_buf()?;
+
+		if a_bytes.is_empty() || b_bytes.is_empty() {
+			return Ok(a_bytes.is_empty() && b_bytes.is_empty());
+		}
+
+		let len = a_bytes.len().min(b_bytes.len());
+		if a_bytes[..len] != b_bytes[..len] {
+			return Ok(false);
+		}
+
+		a.consume(len);
+		b.consume(len);
+	}
+}
+
+/// The shape of a tree as compared after copying: every entry below `root` with its size, or
+/// symlink target. File contents are compared by `verify_copy`.
+#[derive(Debug, PartialEq, Eq)]
+enum Entry {
+	Dir,
+	File(u64),
+	Symlink(PathBuf),
+}
+
+fn snapshot(root: &Path) -> io::Result<BTreeMap<PathBuf, Entry>> {
+	let mut entries = BTreeMap::new();
+	let mut pending = vec![root.to_path_buf()];
+
+	while let Some(dir) = pending.pop() {
+		for entry in fs::read_dir(&dir)? {
+			let entry = entry?;
+			let metadata = fs::symlink_metadata(entry.path())?;
+			let relative = entry.path().strip_prefix(root).expect("entry below root").to_path_buf();
+
+			let kind = if metadata.is_symlink() {
+				Entry::Symlink(fs::read_link(entry.path())?)
+			} else if metadata.is_dir() {
+				pending.push(entry.path());
+				Entry::Dir
+			} else {
+				Entry::File(metadata.len())
+			};
+
+			entries.insert(relative, kind);
+		}
+	}
+
+	Ok(entries)
+}
+
+/// Points git's worktree links at the repo's new location, like `git worktree repair`. For a
+/// linked worktree the main repo's admin entry is updated; for a main repo, the `.git` files
+/// of its worktrees are.
+pub fn fix_worktree_links(repo_path: &Path) -> io::Result<()> {
+	let repo_path = fs::canonicalize(repo_path)?;
+	let dot_git = repo_path.join(".git");
+
+	if dot_git.is_file() {
+		let contents = fs::read_to_string(&dot_git)?;
+		let Some(admin_dir) = contents.trim().strip_prefix("gitdir:") else {
+			return Ok(());
+		};
+
+		// Relative links are relative to the worktree; joining an absolute path replaces it
+		let admin_dir = repo_path.join(admin_dir.trim());
+
+		// Submodules use `.git` files too, but their git dir has no `commondir`
+		if admin_dir.join("commondir").is_file() {
+			fs::write(admin_dir.join("gitdir"), format!("{}\n", dot_git.display()))?;
+		}
+
+		return Ok(());
+	}
+
+	let Ok(worktrees) = fs::read_dir(dot_git.join("worktrees")) else {
+		return Ok(());
+	};
+
+	for worktree in worktrees.flatten() {
+		let Ok(worktree_dot_git) = fs::read_to_string(worktree.path().join("gitdir")) else {
+			continue;
+		};
+		let worktree_dot_git = PathBuf::from(worktree_dot_git.trim());
+
+		if worktree_dot_git.is_file() {
+			fs::write(&worktree_dot_git, format!("gitdir: {}\n", worktree.path().display()))?;
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use tempfile::tempdir;
+
+	#[test]
+	fn test_copy_verify_delete_moves_tree() {
+		let temp = tempdir().expect("get tmp dir failed");
+		let from = temp.path().join("from");
+		let to = temp.path().join("to");
+
+		fs::create_dir_all(from.join(".git/refs")).expect("create test tree");
+		fs::write(from.join(".git/HEAD"), "ref: refs/heads/main\n").expect("create test tree");
+		fs::write(from.join("README.md"), "readme").expect("create test tree");
+
+		copy_verify_delete(&from, &to).expect("copy failed");
+
+		assert!(!from.exists());
+		assert_eq!("ref: refs/heads/main\n", fs::read_to_string(to.join(".git/HEAD")).expect("HEAD copied"));
+		assert!(to.join(".git/refs").is_dir());
+	}
+
+	#[test]
+	fn test_verify_copy_compares_contents() {
+		let temp = tempdir().expect("get tmp dir failed");
+		let from = temp.path().join("from");
+		let to = temp.path().join("to");
+
+		fs::create_dir_all(&from).expect("create test tree");
+		fs::write(from.join("README.md"), "readme").expect("create test tree");
+		copy_dir(&from, &to).expect("copy failed");
+
+		assert!(verify_copy(&from, &to).expect("verify failed"));
+
+		// Same size, different bytes
+		fs::write(to.join("README.md"), "README").expect("change copy");
+		assert!(!verify_copy(&from, &to).expect("verify failed"));
+	}
+}
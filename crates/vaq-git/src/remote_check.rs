@@ -0,0 +1,254 @@
+use crate::exec::{exists, run_collecting, RunStatus};
+use crate::repos::VaqRepo;
+use crate::settings::VaqSettings;
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use derive_more::Display;
+
+/// What connecting to a remote showed.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+pub enum RemoteHealth {
+	#[display("ok")]
+	Reachable,
+
+	/// The host redirected to another URL, usually because the repo was renamed or transferred.
+	#[display("moved to {url}")]
+	Moved { url: String },
+
+	#[display("not found")]
+	NotFound,
+
+	#[display("authentication failed")]
+	AuthFailed,
+
+	#[display("unreachable: {_0}")]
+	Unreachable(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct RemoteCheck {
+	pub repo: PathBuf,
+	pub remote: String,
+	pub url: String,
+	pub health: RemoteHealth,
+
+	/// The branch the remote's HEAD points to.
+	pub default_branch: Option<String>,
+
+	/// Whether the local branch named like `default_branch` tracks it, for the remote that
+	/// branch tracks. `None` for the other remotes (e.g. `upstream` next to a fork's `origin`)
+	/// and when there is nothing to compare (no clone, no default branch, no upstream).
+	pub tracked: Option<bool>,
+}
+
+impl RemoteCheck {
+	pub fn is_ok(&self) -> bool {
+		self.health == RemoteHealth::Reachable && self.tracked != Some(false)
+	}
+}
+
+/// How many remotes are checked at once.
+const WORKERS: usize = 8;
+
+/// How long `git ls-remote` may take before the remote counts as unreachable.
+const LS_REMOTE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connects to every configured remote of `repos`, a few at a time, with `git ls-remote`,
+/// which follows the user's git configuration for credentials. The URLs checked are the ones
+/// after the `url_rewrites` of `settings`, as used by git. Nothing is fetched.
+pub fn check(repos: &[VaqRepo], settings: &VaqSettings) -> Vec<RemoteCheck> {
+	let remotes: Vec<(&Path, &str, String)> = repos
+		.iter()
+		.flat_map(|repo| repo.remotes.items.values().map(move |remote| (repo, remote)))
+		.map(|(repo, remote)| (repo.path.as_path(), remote.name.as_str(), settings.rewrite_url(&remote.url).to_string()))
+		.collect();
+
+	let next = AtomicUsize::new(0);
+	let checks = Mutex::new(Vec::new());
+
+	thread::scope(|scope| {
+		for _ in 0..WORKERS.min(remotes.len()) {
+			scope.spawn(|| {
+				while let Some((repo_path, remote_name, url)) = remotes.get(next.fetch_add(1, Ordering::Relaxed)) {
+					let check = check_remote(repo_path, remote_name, url);
+					checks.lock().expect("remote check thread panicked").push(check);
+				}
+			});
+		}
+	});
+
+	let mut checks = checks.into_inner().expect("remote check thread panicked");
+	checks.sort_by(|a, b| (&a.repo, &a.remote).cmp(&(&b.repo, &b.remote)));
+	checks
+}
+
+fn check_remote(repo_path: &Path, remote_name: &str, url: &str) -> RemoteCheck {
+	let has_clone = exists(repo_path);
+
+	let mut command = Command::new("git");
+	command
+		.args(["ls-remote", "--symref", url, "HEAD"])
+		.env("GIT_TERMINAL_PROMPT", "0")
+		.stdin(Stdio::null())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped());
+
+	// Fail instead of asking for a password or host key confirmation
+	if env::var_os("GIT_SSH_COMMAND").is_none() {
+		command.env("GIT_SSH_COMMAND", "ssh -o BatchMode=yes");
+	}
+
+	if has_clone {
+		command.current_dir(repo_path);
+	}
+
+	let (health, default_branch) = match run_collecting(&mut command, Some(LS_REMOTE_TIMEOUT)) {
+		Ok((_, _, status @ RunStatus::TimedOut(_))) => (RemoteHealth::Unreachable(status.to_string()), None),
+		Ok((stdout, stderr, status)) => parse_ls_remote(status.success(), &stdout, &stderr),
+		Err(error) => (RemoteHealth::Unreachable(format!("failed to run git: {error}")), None),
+	};
+
+	let tracked = match &default_branch {
+		Some(branch) if has_clone => local_upstream(repo_path, branch)
+			.filter(|(upstream_remote, _)| upstream_remote == remote_name)
+			.map(|(_, upstream_branch)| upstream_branch == *branch),
+		_ => None,
+	};
+
+	RemoteCheck {
+		repo: repo_path.to_path_buf(),
+		remote: remote_name.to_string(),
+		url: url.to_string(),
+		health,
+		default_branch,
+		tracked,
+	}
+}
+
+/// The remote and remote branch local `branch` tracks (e.g. `("origin", "main")`), or `None`
+/// if it tracks nothing or doesn't exist.
+fn local_upstream(repo_path: &Path, branch: &str) -> Option<(String, String)> {
+	let branch_config = |key: &str| {
+		let output = Command::new("git")
+			.args(["config", "--get", &format!("branch.{branch}.{key}")])
+			.current_dir(repo_path)
+			.stderr(Stdio::null())
+			.output()
+			.ok()
+			.filter(|output| output.status.success())?;
+
+		Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+	};
+
+	let remote = branch_config("remote")?;
+	let merge = branch_config("merge")?;
+	let remote_branch = merge.strip_prefix("refs/heads/").unwrap_or(&merge).to_string();
+
+	Some((remote, remote_branch))
+}
+
+/// Interprets `git ls-remote --symref <url> HEAD`. The default branch comes from the
+/// `ref: refs/heads/<branch>\tHEAD` line; empty repos have none.
+fn parse_ls_remote(success: bool, stdout: &str, stderr: &str) -> (RemoteHealth, Option<String>) {
+	if !success {
+		return (classify_failure(stderr), None);
+	}
+
+	let default_branch = stdout
+		.lines()
+		.filter_map(|line| line.strip_prefix("ref: refs/heads/"))
+		.find_map(|rest| rest.strip_suffix("\tHEAD"))
+		.map(|branch| branch.to_string());
+
+	let redirect = stderr
+		.lines()
+		.find_map(|line| line.trim().strip_prefix("warning: redirecting to "));
+
+	let health = match redirect {
+		Some(url) => RemoteHealth::Moved { url: url.to_string() },
+		None => RemoteHealth::Reachable,
+	};
+
+	(health, default_branch)
+}
+
+/// Classifies a failed `git ls-remote` by the messages git and the big hosts print for a
+/// missing repo or refused credentials. Anything else, e.g. a host named `404.example.org`
+/// that can't be resolved, is unreachable.
+fn classify_failure(stderr: &str) -> RemoteHealth {
+	let lines: Vec<String> = stderr.lines().map(|line| line.trim().to_lowercase()).collect();
+
+	let any = |matches: &dyn Fn(&str) -> bool| lines.iter().any(|line| matches(line));
+
+	let not_found = any(&|line| {
+		(line.starts_with("fatal: repository '") && line.ends_with("' not found"))
+			|| line.ends_with("does not appear to be a git repository")
+			|| line.starts_with("remote: repository not found")
+			|| line.starts_with("remote: the project you were looking for could not be found")
+			|| line.ends_with("the requested url returned error: 404")
+	});
+
+	let auth_failed = any(&|line| {
+		line.starts_with("fatal: authentication failed for ")
+			|| line.contains(": permission denied (")
+			|| line.starts_with("fatal: could not read username for ")
+			|| line.ends_with("terminal prompts disabled")
+			|| line.ends_with("the requested url returned error: 401")
+			|| line.ends_with("the requested url returned error: 403")
+	});
+
+	if not_found {
+		RemoteHealth::NotFound
+	} else if auth_failed {
+		RemoteHealth::AuthFailed
+	} else {
+		let message = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("unknown error");
+		RemoteHealth::Unreachable(message.trim().trim_start_matches("fatal: ").to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn default_branch_from_symref() {
+		let stdout = "ref: refs/heads/trunk\tHEAD\n0123456789abcdef0123456789abcdef01234567\tHEAD\n";
+
+		assert_eq!((RemoteHealth::Reachable, Some("trunk".to_string())), parse_ls_remote(true, stdout, ""));
+		assert_eq!((RemoteHealth::Reachable, None), parse_ls_remote(true, "", ""));
+	}
+
+	#[test]
+	fn redirect_means_moved() {
+		let stderr = "warning: redirecting to https://github.com/new-owner/api.git/\n";
+
+		assert_eq!(
+			RemoteHealth::Moved { url: "https://github.com/new-owner/api.git/".to_string() },
+			parse_ls_remote(true, "ref: refs/heads/main\tHEAD\n", stderr).0,
+		);
+	}
+
+	#[test]
+	fn classify_failures() {
+		assert_eq!(RemoteHealth::NotFound, classify_failure("remote: Repository not found.\nfatal: repository 'https://github.com/a/b/' not found\n"));
+		assert_eq!(RemoteHealth::NotFound, classify_failure("fatal: '/tmp/gone' does not appear to be a git repository\n"));
+		assert_eq!(RemoteHealth::AuthFailed, classify_failure("git@github.com: Permission denied (publickey).\nfatal: Could not read from remote repository.\n"));
+		assert_eq!(RemoteHealth::AuthFailed, classify_failure("fatal: unable to access 'https://example.org/a/b/': The requested URL returned error: 403\n"));
+		assert_eq!(
+			RemoteHealth::Unreachable("unable to access 'https://404.example.org/a/b/': Could not resolve host: 404.example.org".to_string()),
+			classify_failure("fatal: unable to access 'https://404.example.org/a/b/': Could not resolve host: 404.example.org\n"),
+		);
+		assert_eq!(
+			RemoteHealth::Unreachable("unable to access 'https://nowhere.invalid/': Could not resolve host: nowhere.invalid".to_string()),
+			classify_failure("fatal: unable to access 'https://nowhere.invalid/': Could not resolve host: nowhere.invalid\n"),
+		);
+	}
+}
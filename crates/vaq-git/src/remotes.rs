@@ -3,7 +3,7 @@ use crate::vaq_types::{VaqUrl, VaqUrlBuf};
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::error::Error;
-use std::rc::Rc;
+use std::sync::Arc;
 use std::vec::IntoIter;
 
 use bstr::{BStr, BString};
@@ -16,10 +16,13 @@ use thiserror::Error;
 #[display("({name}) {url}")]
 pub struct VaqRemote {
 	#[display("{}")]
-	pub name: Rc<String>,
+	pub name: Arc<String>,
 
 	#[display("{}")]
 	pub url: VaqUrlBuf,
+
+	/// Separate push URL (`push_url` in `.vaquera.toml`), when pushes don't go to `url`.
+	pub push_url: Option<VaqUrlBuf>,
 }
 
 #[derive(Error, Debug)]
@@ -40,7 +43,11 @@ pub enum VaqRemoteError {
 
 impl VaqRemote {
 	pub fn new(name: String, url: VaqUrlBuf) -> Self {
-		VaqRemote { name: Rc::from(name), url }
+		VaqRemote { name: Arc::from(name), url, push_url: None }
+	}
+
+	pub fn with_push_url(self, push_url: Option<VaqUrlBuf>) -> Self {
+		VaqRemote { push_url, ..self }
 	}
 }
 
@@ -54,7 +61,12 @@ impl <'a> TryFrom<&'a Remote<'a>> for VaqRemote {
 		let url = VaqUrlBuf::try_from(url_bstr)
 			.map_err(|e| VaqRemoteError::InvalidUrl(url_str.into(), e.into()))?;
 
-		Ok(VaqRemote::new(name, url))
+		let push_url = value.pushurl()
+			.map(|push_url_str| VaqUrlBuf::try_from(BString::new(push_url_str.into()))
+				.map_err(|e| VaqRemoteError::InvalidUrl(push_url_str.into(), e.into())))
+			.transpose()?;
+
+		Ok(VaqRemote::new(name, url).with_push_url(push_url))
 	}
 }
 
@@ -63,13 +75,47 @@ pub struct VaqRemoteSlice<'a>(pub &'a [VaqRemote]);
 
 #[derive(Clone, Debug)]
 pub struct VaqRemotes {
-	pub(crate) items: BTreeMap<Rc<String>, VaqRemote>,
+	pub(crate) items: BTreeMap<Arc<String>, VaqRemote>,
+}
+
+/// A change to one remote, applied to each selected repo. URLs are templates expanded per
+/// repo, see `template::expand`.
+#[derive(Clone, Debug, Display)]
+pub enum RemoteEdit {
+	#[display("add {name} {url}")]
+	Add { name: String, url: String },
+
+	#[display("rename {name} {new_name}")]
+	Rename { name: String, new_name: String },
+
+	#[display("remove {name}")]
+	Remove { name: String },
+
+	#[display("set-url {name} {url}")]
+	SetUrl { name: String, url: String },
 }
 
 impl VaqRemotes {
 	pub fn new() -> Self {
 		VaqRemotes { items: BTreeMap::new() }
 	}
+
+	pub fn get(&self, name: &str) -> Option<&VaqRemote> {
+		self.items.values().find(|remote| remote.name.as_str() == name)
+	}
+
+	pub fn contains(&self, name: &str) -> bool {
+		self.get(name).is_some()
+	}
+
+	/// Remotes with their names, ordered by name.
+	pub fn iter(&self) -> impl Iterator<Item = (&str, &VaqRemote)> {
+		self.items.iter().map(|(name, remote)| (name.as_str(), remote))
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.items.is_empty()
+	}
 }
 
 impl<'a> From<VaqRemoteSlice<'a>> for VaqRemotes {
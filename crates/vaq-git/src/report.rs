@@ -0,0 +1,215 @@
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use derive_more::Display;
+use serde::Serializer;
+use serde_derive::Serialize;
+
+/// What happened in one repo during `exec`, for the summary table and reports.
+#[derive(Clone, Debug, Serialize)]
+pub struct RepoResult {
+	pub path: PathBuf,
+	pub status: ResultStatus,
+	/// `None` if the command didn't run, timed out or was ended by a signal.
+	pub exit_code: Option<i32>,
+	/// Time taken by all attempts together.
+	#[serde(rename = "seconds", serialize_with = "as_seconds")]
+	pub duration: Duration,
+	pub attempts: usize,
+	pub first_error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResultStatus {
+	#[display("passed")]
+	Passed,
+	#[display("failed")]
+	Failed,
+	#[display("timed out")]
+	TimedOut,
+	#[display("skipped")]
+	Skipped,
+	/// Not started because an earlier repo failed, see `ExecOptions::fail_fast`.
+	#[display("not run")]
+	NotRun,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+	Junit,
+	Json,
+}
+
+impl ReportFormat {
+	/// Where the report goes unless told otherwise.
+	pub fn default_file(&self) -> PathBuf {
+		match self {
+			ReportFormat::Junit => PathBuf::from("vaquera-exec.xml"),
+			ReportFormat::Json => PathBuf::from("vaquera-exec.json"),
+		}
+	}
+
+	pub fn render(&self, results: &[RepoResult]) -> String {
+		match self {
+			ReportFormat::Junit => to_junit(results),
+			ReportFormat::Json => serde_json::to_string_pretty(results).expect("Failed to serialize exec results"),
+		}
+	}
+}
+
+fn as_seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// One line per repo: path, exit code (or why there is none), duration and first error line.
+pub fn summary_table(results: &[RepoResult]) -> String {
+	let path_width = results.iter().map(|result| result.path.as_os_str().len()).max().unwrap_or(0).max(4);
+	let mut table = format!("{:<path_width$}  {:<9}  {:>8}  ERROR\n", "REPO", "EXIT", "DURATION");
+
+	for result in results {
+		let exit = match (result.exit_code, result.status) {
+			(Some(code), _) => code.to_string(),
+			(None, ResultStatus::Passed | ResultStatus::Failed) => "-".to_string(),
+			(None, status) => status.to_string(),
+		};
+		let duration = format!("{:.1}s", result.duration.as_secs_f64());
+		let error = result.first_error.as_deref().unwrap_or_default();
+
+		table.push_str(&format!("{:<path_width$}  {exit:<9}  {duration:>8}  {error}", result.path.display()).trim_end());
+		table.push('\n');
+	}
+
+	table
+}
+
+/// A JUnit XML report with one test case per repo, as read by most CI servers.
+pub fn to_junit(results: &[RepoResult]) -> String {
+	let count = |status: ResultStatus| results.iter().filter(|result| result.status == status).count();
+	let failures = count(ResultStatus::Failed) + count(ResultStatus::TimedOut);
+	let skipped = count(ResultStatus::Skipped) + count(ResultStatus::NotRun);
+	let time: f64 = results.iter().map(|result| result.duration.as_secs_f64()).sum();
+
+	let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+	xml.push_str(&format!(
+		"<testsuite name=\"vaquera exec\" tests=\"{}\" failures=\"{failures}\" errors=\"0\" skipped=\"{skipped}\" time=\"{time:.3}\">\n",
+		results.len()
+	));
+
+	for result in results {
+		let name = escape_xml(&result.path.display().to_string());
+		let time = result.duration.as_secs_f64();
+		let message = escape_xml(result.first_error.as_deref().unwrap_or_default());
+
+		xml.push_str(&format!("  <testcase classname=\"vaquera.exec\" name=\"{name}\" time=\"{time:.3}\""));
+
+		match result.status {
+			ResultStatus::Passed => xml.push_str("/>\n"),
+			ResultStatus::Failed | ResultStatus::TimedOut => xml.push_str(&format!(
+				">\n    <failure message=\"{message}\" type=\"{}\"/>\n  </testcase>\n",
+				describe(result),
+			)),
+			ResultStatus::Skipped | ResultStatus::NotRun => {
+				xml.push_str(&format!(">\n    <skipped message=\"{}\"/>\n  </testcase>\n", result.status))
+			}
+		}
+	}
+
+	xml.push_str("</testsuite>\n");
+	xml
+}
+
+fn describe(result: &RepoResult) -> String {
+	match result.exit_code {
+		Some(code) => format!("exit code {code}"),
+		None => result.status.to_string(),
+	}
+}
+
+fn escape_xml(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+		.replace('\'', "&apos;")
+}
+
+/// The file name for the output of the repo at `path` in a log folder: the folders of its path
+/// joined by `%2F`, with `%` itself as `%25`, so `services/api` logs to
+/// `services%2Fapi.stdout.log` and no two paths share a file. Roots and prefixes are left out,
+/// so an absolute path still names a file inside the log folder.
+pub fn log_file_name(path: &Path, stream: &str) -> String {
+	let name: Vec<String> = path.components()
+		.filter_map(|part| match part {
+			Component::Normal(name) => Some(name.to_string_lossy().replace('%', "%25")),
+			_ => None,
+		})
+		.collect();
+
+	format!("{}.{stream}.log", name.join("%2F"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn result(path: &str, status: ResultStatus, exit_code: Option<i32>, first_error: Option<&str>) -> RepoResult {
+		RepoResult {
+			path: PathBuf::from(path),
+			status,
+			exit_code,
+			duration: Duration::from_millis(1500),
+			attempts: 1,
+			first_error: first_error.map(str::to_string),
+		}
+	}
+
+	#[test]
+	fn summary_table_lines() {
+		let results = [
+			result("services/api", ResultStatus::Passed, Some(0), None),
+			result("web", ResultStatus::Failed, Some(2), Some("npm ERR! missing script")),
+			result("docs", ResultStatus::NotRun, None, None),
+		];
+
+		assert_eq!(
+			"REPO          EXIT       DURATION  ERROR\n\
+			 services/api  0              1.5s\n\
+			 web           2              1.5s  npm ERR! missing script\n\
+			 docs          not run        1.5s\n",
+			summary_table(&results)
+		);
+	}
+
+	#[test]
+	fn junit_escapes_and_counts() {
+		let results = [
+			result("api", ResultStatus::Passed, Some(0), None),
+			result("web", ResultStatus::TimedOut, None, Some("<timed out> & killed")),
+		];
+
+		assert_eq!(
+			"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+			 <testsuite name=\"vaquera exec\" tests=\"2\" failures=\"1\" errors=\"0\" skipped=\"0\" time=\"3.000\">\n  \
+			 <testcase classname=\"vaquera.exec\" name=\"api\" time=\"1.500\"/>\n  \
+			 <testcase classname=\"vaquera.exec\" name=\"web\" time=\"1.500\">\n    \
+			 <failure message=\"&lt;timed out&gt; &amp; killed\" type=\"timed out\"/>\n  \
+			 </testcase>\n\
+			 </testsuite>\n",
+			to_junit(&results)
+		);
+	}
+
+	#[test]
+	fn log_file_names() {
+		assert_eq!("services%2Fapi.stderr.log", log_file_name(Path::new("services/api"), "stderr"));
+		assert_eq!("api.stdout.log", log_file_name(Path::new("api"), "stdout"));
+		assert_eq!("srv%2Fapi.stdout.log", log_file_name(Path::new("/srv/api"), "stdout"));
+	}
+
+	#[test]
+	fn log_file_names_are_unique() {
+		assert_ne!(log_file_name(Path::new("a__b"), "stdout"), log_file_name(Path::new("a/b"), "stdout"));
+		assert_ne!(log_file_name(Path::new("a%2Fb"), "stdout"), log_file_name(Path::new("a/b"), "stdout"));
+	}
+}
@@ -27,6 +27,10 @@ pub struct VaqRepo {
 
 	pub tags: VaqTagsBuf,
 	pub remotes: VaqRemotes,
+
+	/// Free-form values from the repo's `meta` table, for `{meta.<key>}` in templates.
+	#[builder(default)]
+	pub meta: BTreeMap<String, String>,
 }
 
 impl VaqRepoBuilder {
@@ -91,6 +95,10 @@ impl VaqRepo {
 		);
 	}
 
+	pub(crate) fn remove_remote(&mut self, name: &str) -> Option<VaqRemote> {
+		self.remotes.items.remove(&name.to_string())
+	}
+
 	pub(crate) fn replace_remotes(&mut self, remotes_arg: VaqRemoteSlice) {
 		self.remotes.items.clear();
 		let remotes: VaqRemotes = remotes_arg.into();
@@ -116,6 +124,10 @@ impl VaqRepos {
 		VaqRepos { items: repos }
 	}
 
+	pub fn as_vec(&self) -> &VaqRepoVec {
+		&self.items
+	}
+
 	pub fn find<F>(&mut self, predicate: F) -> Option<&mut VaqRepo>
 	where
 		F: FnMut(&&mut VaqRepo) -> bool,
@@ -164,6 +176,55 @@ impl VaqRepos {
 		}
 	}
 
+	pub fn remove_by_paths(&mut self, repo_paths: &[PathBuf]) {
+		for repo_path in repo_paths {
+			match self.index_by_path(repo_path) {
+				Some(ix) => {
+					self.items.remove(ix);
+					info!("Removed {}", repo_path.display());
+				}
+				None => {
+					info!("Repo already absent, skipped: {}", repo_path.display())
+				}
+			}
+		}
+	}
+
+	/// Changes the path of the repo at `old_path`, and keeps the list sorted by path. A repo
+	/// named after its old folder is renamed after the new one; a name of its own is kept.
+	/// Returns `false` if there is no repo at `old_path`.
+	pub fn set_path(&mut self, old_path: &Path, new_path: &Path) -> bool {
+		let Some(repo) = self.find_by_path(old_path) else {
+			return false;
+		};
+
+		let old_folder = old_path.file_name().and_then(|os| os.to_str());
+		let new_folder = new_path.file_name().and_then(|os| os.to_str());
+
+		repo.path = new_path.to_path_buf();
+		if let (Some(old_folder), Some(new_folder)) = (old_folder, new_folder) {
+			if repo.name == old_folder {
+				repo.name = new_folder.to_string();
+			}
+		}
+
+		self.items.sort_by(|a, b| a.path.cmp(&b.path));
+		true
+	}
+
+	/// Removes every repo matching `predicate` and returns them.
+	pub fn prune<F>(&mut self, mut predicate: F) -> VaqRepoVec
+	where
+		F: FnMut(&VaqRepo) -> bool,
+	{
+		let (pruned, kept) = std::mem::take(&mut self.items)
+			.into_iter()
+			.partition(|repo| predicate(repo));
+
+		self.items = kept;
+		pruned
+	}
+
 	pub fn add_tag(
 		&mut self,
 		tag_name: &str,
@@ -201,14 +262,17 @@ impl VaqRepos {
 	) -> Result<(), VaqError>
 	where
 		P: Fn(&str, &mut VaqRepo) {
+		// By path first, as names can be shared (e.g. `libs/api` and `services/api`)
 		for repo_name in repo_names {
-			let repo = self.find_by_name(repo_name.as_str()).ok_or_else(|| {
-				VaqError::State {
-					message: format!("Repo '{repo_name}' not found"),
-				}
-			})?;
-
-			action(tag_name, repo);
+			let index = self.index_by_path(Path::new(&repo_name))
+				.or_else(|| self.index_by_name(&repo_name))
+				.ok_or_else(|| {
+					VaqError::State {
+						message: format!("Repo '{repo_name}' not found"),
+					}
+				})?;
+
+			action(tag_name, &mut self.items[index]);
 		}
 
 		Ok(())
@@ -0,0 +1,139 @@
+use crate::vaq_types::VaqUrlBuf;
+
+use std::path::PathBuf;
+
+use derive_more::Display;
+use gix_url::Scheme;
+use serde_derive::{Deserialize, Serialize};
+
+/// An `insteadOf`-style rule: remote URLs starting with `from` are rewritten to start with `to`.
+///
+/// `from` only matches URLs of its own transport, in either spelling for SSH (scp-like and
+/// `ssh://`). A rule switches a remote between SSH and HTTPS only when written that way, e.g.
+/// from `git@host:` to `https://host/`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct UrlRewrite {
+	pub from: String,
+	pub to: String,
+}
+
+/// Where a rewritten URL was stored.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+pub enum RewriteSide {
+	#[display("config")]
+	Config,
+
+	#[display("config push")]
+	ConfigPush,
+
+	#[display("git")]
+	Git,
+
+	#[display("git push")]
+	GitPush,
+}
+
+#[derive(Clone, Debug, Display)]
+#[display("{} {name} ({side}): {old_url} -> {new_url}", path.display())]
+pub struct RewrittenRemote {
+	pub path: PathBuf,
+	pub name: String,
+	pub side: RewriteSide,
+	pub old_url: String,
+	pub new_url: String,
+}
+
+impl UrlRewrite {
+	pub fn new(from: &str, to: &str) -> Self {
+		UrlRewrite { from: from.to_string(), to: to.to_string() }
+	}
+
+	/// The rewritten URL, or `None` if `url` doesn't start with `from` in any of its spellings.
+	pub fn apply(&self, url: &VaqUrlBuf) -> Option<VaqUrlBuf> {
+		if self.from.is_empty() {
+			return None;
+		}
+
+		spellings(url)
+			.iter()
+			.find_map(|spelling| spelling.strip_prefix(self.from.as_str()))
+			.map(|rest| format!("{}{}", self.to, rest))
+			.and_then(|rewritten| VaqUrlBuf::try_from(rewritten.as_str()).ok())
+	}
+
+	/// Like `apply`, for URLs read as text from git.
+	pub fn apply_str(&self, url: &str) -> Option<VaqUrlBuf> {
+		VaqUrlBuf::try_from(url).ok().and_then(|url| self.apply(&url))
+	}
+}
+
+/// The URL as written, followed for SSH URLs by both SSH spellings. Other transports, and local
+/// paths, only match as written.
+fn spellings(url: &VaqUrlBuf) -> Vec<String> {
+	let mut spellings = vec![url.to_string()];
+
+	if url.url.scheme != Scheme::Ssh {
+		return spellings;
+	}
+
+	let Some(host) = url.url.host() else {
+		return spellings;
+	};
+
+	let host_port = match url.url.port {
+		Some(port) => format!("{host}:{port}"),
+		None => host.to_string(),
+	};
+	let user = url.url.user().map(|user| format!("{user}@")).unwrap_or_default();
+	let path = url.url.path.to_string();
+	let path = path.trim_start_matches('/');
+
+	spellings.extend([
+		format!("{user}{host}:{path}"),
+		format!("ssh://{user}{host_port}/{path}"),
+	]);
+
+	spellings
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rewrite(rule: &UrlRewrite, url: &str) -> Option<String> {
+		rule.apply_str(url).map(|url| url.to_string())
+	}
+
+	#[test]
+	fn rewrite_host() {
+		let rule = UrlRewrite::new("git@git.old.corp:", "git@git.new.corp:");
+
+		assert_eq!(Some("git@git.new.corp:team/api.git".to_string()), rewrite(&rule, "git@git.old.corp:team/api.git"));
+		assert_eq!(None, rewrite(&rule, "git@github.com:team/api.git"));
+	}
+
+	#[test]
+	fn rewrite_between_ssh_and_https() {
+		let to_https = UrlRewrite::new("git@git.old.corp:", "https://git.new.corp/");
+		let to_ssh = UrlRewrite::new("https://git.old.corp/", "git@git.new.corp:");
+
+		assert_eq!(Some("https://git.new.corp/team/api.git".to_string()), rewrite(&to_https, "git@git.old.corp:team/api.git"));
+		assert_eq!(Some("git@git.new.corp:team/api.git".to_string()), rewrite(&to_ssh, "https://git.old.corp/team/api.git"));
+	}
+
+	#[test]
+	fn https_rule_leaves_ssh_url_alone() {
+		let rule = UrlRewrite::new("https://git.old.corp/", "https://git.new.corp/");
+
+		assert_eq!(None, rewrite(&rule, "ssh://git@git.old.corp/team/api.git"));
+		assert_eq!(None, rewrite(&rule, "git@git.old.corp:team/api.git"));
+	}
+
+	#[test]
+	fn ssh_rule_matches_both_ssh_spellings() {
+		let rule = UrlRewrite::new("git@git.old.corp:", "git@git.new.corp:");
+
+		assert_eq!(Some("git@git.new.corp:team/api.git".to_string()), rewrite(&rule, "ssh://git@git.old.corp/team/api.git"));
+		assert_eq!(None, rewrite(&rule, "https://git.old.corp/team/api.git"));
+	}
+}
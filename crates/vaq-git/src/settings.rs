@@ -0,0 +1,59 @@
+use crate::commands::CustomCommand;
+use crate::hooks::Hooks;
+use crate::remotes::VaqRemotes;
+use crate::rewrite::UrlRewrite;
+use crate::vaq_types::VaqUrlBuf;
+
+use std::collections::BTreeMap;
+
+use serde_derive::{Deserialize, Serialize};
+
+/// Workspace-wide settings stored next to the repo list in `.vaquera.toml`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct VaqSettings {
+	/// Where `clone <url>` puts repos and `relayout` moves them, e.g. `{host}/{owner}/{name}`.
+	/// See `template::expand_layout`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub layout: Option<String>,
+
+	/// Rules applied to remote URLs when cloning, first match wins. `doctor` and `sync` compare
+	/// git with the rewritten URLs, so cloned repos don't show up as drifted:
+	///
+	/// ```toml
+	/// [[url_rewrites]]
+	/// from = "git@git.old.corp:"
+	/// to = "https://git.new.corp/"
+	/// ```
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub url_rewrites: Vec<UrlRewrite>,
+
+	/// Named commands, see `CustomCommand`.
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub commands: BTreeMap<String, CustomCommand>,
+
+	/// Commands run in a repo around vaquera's operations, see `HookEvent`.
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub hooks: Hooks,
+}
+
+impl VaqSettings {
+	/// `url` with the first matching rewrite rule applied, or unchanged if none matches.
+	pub fn rewrite_url(&self, url: &VaqUrlBuf) -> VaqUrlBuf {
+		self.url_rewrites
+			.iter()
+			.find_map(|rule| rule.apply(url))
+			.unwrap_or_else(|| url.clone())
+	}
+
+	/// `remotes` as cloned, with `rewrite_url` applied to their URLs and push URLs.
+	pub fn rewrite_remotes(&self, remotes: &VaqRemotes) -> VaqRemotes {
+		let mut rewritten = remotes.clone();
+
+		for remote in rewritten.items.values_mut() {
+			remote.url = self.rewrite_url(&remote.url);
+			remote.push_url = remote.push_url.as_ref().map(|url| self.rewrite_url(url));
+		}
+
+		rewritten
+	}
+}
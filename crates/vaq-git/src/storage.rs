@@ -1,10 +1,12 @@
 use std::fs;
+use std::io;
 
 // Abstract away storage to allow testing via dependency injection
 pub trait Storage {
 	fn exists(&self) -> bool;
-	fn save(&self, state_toml: String);
+	fn save(&self, state_toml: String) -> io::Result<()>;
 	fn read(&self) -> String;
+	fn remove(&self);
 }
 
 // The struct used in production code
@@ -17,13 +19,17 @@ impl Storage for Box<dyn Storage + 'static> {
 		self.as_ref().exists()
 	}
 
-	fn save(&self, state_toml: String) {
+	fn save(&self, state_toml: String) -> io::Result<()> {
 		self.as_ref().save(state_toml)
 	}
 
 	fn read(&self) -> String {
 		self.as_ref().read()
 	}
+
+	fn remove(&self) {
+		self.as_ref().remove()
+	}
 }
 
 // The implementation used in production code
@@ -32,12 +38,18 @@ impl<'a> Storage for StorageImpl<'a> {
 		std::path::Path::new(self.path).exists()
 	}
 
-	fn save(&self, state_toml: String) {
+	fn save(&self, state_toml: String) -> io::Result<()> {
 		fs::write(self.path, state_toml)
-			.unwrap_or_else(|_| panic!("Failed to write {}", self.path));
 	}
 
 	fn read(&self) -> String {
 		fs::read_to_string(self.path).expect("Failed to read state file {}")
 	}
+
+	fn remove(&self) {
+		if self.exists() {
+			fs::remove_file(self.path)
+				.unwrap_or_else(|_| panic!("Failed to remove {}", self.path));
+		}
+	}
 }
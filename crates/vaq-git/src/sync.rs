@@ -0,0 +1,112 @@
+use crate::remotes::VaqRemotes;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use derive_more::Display;
+
+/// One difference between the remotes in `.vaquera.toml` and the ones in git, phrased as
+/// what writing the config to git would do.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+pub enum RemoteChange {
+	#[display("+ {name} {url}")]
+	Added { name: String, url: String },
+
+	#[display("~ {name} {git_url} -> {config_url}")]
+	Changed { name: String, git_url: String, config_url: String },
+
+	#[display("- {name} {url}")]
+	Removed { name: String, url: String },
+
+	/// Git pushes somewhere other than the config says. Both URLs are where pushes go, i.e.
+	/// the separate push URL if there is one and the remote URL otherwise.
+	#[display("~ {name} (push) {push_url} -> {config_url}")]
+	PushUrlDiffers { name: String, push_url: String, config_url: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct RepoRemoteDiff {
+	pub path: PathBuf,
+	pub changes: Vec<RemoteChange>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyncWriteOptions {
+	/// Remove remotes from git that are not in the config.
+	pub prune: bool,
+
+	/// Overwrite URLs and push URLs in git that differ from the config.
+	pub update_urls: bool,
+}
+
+/// Which side wins when a remote URL differs between config and git.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncSide {
+	Config,
+	Git,
+	Skip,
+}
+
+impl RemoteChange {
+	pub fn name(&self) -> &str {
+		match self {
+			RemoteChange::Added { name, .. }
+			| RemoteChange::Changed { name, .. }
+			| RemoteChange::Removed { name, .. }
+			| RemoteChange::PushUrlDiffers { name, .. } => name,
+		}
+	}
+}
+
+/// Compares config remotes with git remotes. `push_urls` holds the separate push URLs
+/// configured in git, by remote name.
+pub fn diff_remotes(
+	config: &VaqRemotes,
+	git: &VaqRemotes,
+	push_urls: &BTreeMap<String, String>,
+) -> Vec<RemoteChange> {
+	let names: BTreeSet<_> = config.items.keys().chain(git.items.keys()).collect();
+	let mut changes = Vec::new();
+
+	for name in names {
+		match (config.items.get(name), git.items.get(name)) {
+			(Some(configured), None) => changes.push(RemoteChange::Added {
+				name: name.to_string(),
+				url: configured.url.to_string(),
+			}),
+
+			(None, Some(actual)) => changes.push(RemoteChange::Removed {
+				name: name.to_string(),
+				url: actual.url.to_string(),
+			}),
+
+			(Some(configured), Some(actual)) => {
+				let config_url = configured.url.to_string();
+
+				if config_url != actual.url.to_string() {
+					changes.push(RemoteChange::Changed {
+						name: name.to_string(),
+						git_url: actual.url.to_string(),
+						config_url: config_url.clone(),
+					});
+				}
+
+				let git_push_url = push_urls.get(name.as_str());
+				let config_push_url = configured.push_url.as_ref().map(|url| url.to_string());
+
+				if git_push_url.is_some() || config_push_url.is_some() {
+					let push_url = git_push_url.cloned().unwrap_or_else(|| actual.url.to_string());
+					let config_url = config_push_url.unwrap_or(config_url);
+
+					if push_url != config_url {
+						changes.push(RemoteChange::PushUrlDiffers { name: name.to_string(), push_url, config_url });
+					}
+				}
+			}
+
+			(None, None) => {}
+		}
+	}
+
+	changes
+}
@@ -0,0 +1,136 @@
+use crate::repos::VaqRepo;
+use crate::vaq_types::VaqUrlBuf;
+
+use std::path::{Component, PathBuf};
+
+use bstr::ByteSlice;
+
+/// Expands the per-repo placeholders in `template`, e.g. `git@github.com:myfork/{name}.git`:
+/// `{name}`, `{path}`, `{tags}` (comma-separated), `{remote.<name>}` with the URL of a remote and
+/// `{meta.<key>}` with a value from the repo's `meta` table. Anything else in braces, including
+/// remotes and keys the repo doesn't have, is kept as is.
+pub fn expand(template: &str, repo: &VaqRepo) -> String {
+	expand_with(template, |placeholder| value(placeholder, repo))
+}
+
+/// The value of one placeholder of `expand`, given without braces, e.g. `remote.origin`.
+pub fn value(placeholder: &str, repo: &VaqRepo) -> Option<String> {
+	match placeholder {
+		"name" => Some(repo.name.clone()),
+		"path" => Some(repo.path.to_string_lossy().to_string()),
+		"tags" => Some(repo.tags.join(",")),
+		_ => {
+			if let Some(name) = placeholder.strip_prefix("remote.") {
+				repo.remotes.get(name).map(|remote| remote.url.to_string())
+			} else if let Some(key) = placeholder.strip_prefix("meta.") {
+				repo.meta.get(key).cloned()
+			} else {
+				None
+			}
+		}
+	}
+}
+
+/// Replaces each `{placeholder}` in `template` for which `value` returns something.
+pub fn expand_with<F>(template: &str, value: F) -> String
+where
+	F: Fn(&str) -> Option<String>,
+{
+	let mut expanded = String::with_capacity(template.len());
+	let mut rest = template;
+
+	while let Some(start) = rest.find('{') {
+		let Some(length) = rest[start..].find('}') else {
+			break;
+		};
+
+		let placeholder = &rest[start + 1..start + length];
+		expanded.push_str(&rest[..start]);
+
+		match value(placeholder) {
+			Some(text) => expanded.push_str(&text),
+			None => expanded.push_str(&rest[start..=start + length]),
+		}
+
+		rest = &rest[start + length + 1..];
+	}
+
+	expanded.push_str(rest);
+	expanded
+}
+
+/// Expands a layout template such as `{host}/{owner}/{name}` for a repo cloned from `url`.
+/// `{owner}` is everything between host and repo name (so GitLab subgroups are kept) and
+/// `{name}` is the repo name without `.git`. `None` for URLs without host or owner, like
+/// local paths, and for paths that would leave the workspace through `..` or a root.
+pub fn expand_layout(layout: &str, url: &VaqUrlBuf) -> Option<PathBuf> {
+	let host = url.url.host()?;
+	let path = url.url.path.to_str().ok()?.trim_matches('/');
+	let (owner, name) = path.rsplit_once('/')?;
+	let name = name.strip_suffix(".git").unwrap_or(name);
+
+	if owner.is_empty() || name.is_empty() {
+		return None;
+	}
+
+	let expanded = layout
+		.replace("{host}", host)
+		.replace("{owner}", owner)
+		.replace("{name}", name);
+
+	let expanded = PathBuf::from(expanded);
+	let inside = expanded.components().all(|part| matches!(part, Component::Normal(_)));
+
+	inside.then_some(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::remotes::VaqRemote;
+
+	use std::path::Path;
+
+	#[test]
+	fn expand_placeholders() {
+		let repo = VaqRepo::new(Path::new("services/api")).expect("valid repo");
+
+		assert_eq!("git@github.com:myfork/api.git", expand("git@github.com:myfork/{name}.git", &repo));
+		assert_eq!("https://example.org/services/api", expand("https://example.org/{path}", &repo));
+		assert_eq!("{branch}/api", expand("{branch}/{name}", &repo));
+	}
+
+	#[test]
+	fn expand_tags_remotes_and_meta() {
+		let mut repo = VaqRepo::new(Path::new("services/api")).expect("valid repo");
+		repo.tags.push("backend".to_string());
+		repo.tags.push("rust".to_string());
+		repo.add_remote(VaqRemote::new("origin".to_string(), VaqUrlBuf::try_from("git@github.com:team/api.git").expect("valid url")));
+		repo.meta.insert("owner".to_string(), "payments".to_string());
+
+		assert_eq!("backend,rust", expand("{tags}", &repo));
+		assert_eq!("git@github.com:team/api.git", expand("{remote.origin}", &repo));
+		assert_eq!("{remote.upstream}", expand("{remote.upstream}", &repo));
+		assert_eq!("out/payments-api.log", expand("out/{meta.owner}-{name}.log", &repo));
+		assert_eq!("{meta.team} {unclosed", expand("{meta.team} {unclosed", &repo));
+	}
+
+	#[test]
+	fn expand_layout_from_url() {
+		let layout = "{host}/{owner}/{name}";
+		let url = |text: &str| VaqUrlBuf::try_from(text).expect("valid url");
+
+		assert_eq!(Some(PathBuf::from("github.com/team/api")), expand_layout(layout, &url("git@github.com:team/api.git")));
+		assert_eq!(Some(PathBuf::from("gitlab.com/group/sub/web")), expand_layout(layout, &url("https://gitlab.com/group/sub/web")));
+		assert_eq!(None, expand_layout(layout, &url("/srv/git/api.git")));
+	}
+
+	#[test]
+	fn expand_layout_stays_inside_workspace() {
+		let url = |text: &str| VaqUrlBuf::try_from(text).expect("valid url");
+
+		assert_eq!(None, expand_layout("{host}/{owner}/{name}", &url("https://example.org/team/../../../api.git")));
+		assert_eq!(None, expand_layout("../{name}", &url("git@github.com:team/api.git")));
+		assert_eq!(None, expand_layout("/srv/{name}", &url("git@github.com:team/api.git")));
+	}
+}
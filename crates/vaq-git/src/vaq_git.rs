@@ -1,8 +1,20 @@
-use crate::git::Git;
-use crate::vaq_types::{VaqTags, VaqUrl};
+use crate::commit_log::{self, LogQuery, VaqLog};
+use crate::doctor::{self, is_git_repo, VaqIssue};
+use crate::exec::exists;
+use crate::fork::{self, UPSTREAM};
+use crate::git::{Git, GitError};
+use crate::hooks::{self, HookEvent};
+use crate::journal::{self, FolderMove, Journal, JournalEntry, JournalError};
+use crate::relocate::{self, LayoutCollision, MoveError};
+use crate::vaq_types::{VaqTags, VaqUrl, VaqUrlBuf};
+use crate::remotes::{RemoteEdit, VaqRemote, VaqRemotes};
 use crate::repos::{VaqRepo, VaqRepoBuilderError, VaqRepos};
+use crate::rewrite::{RewriteSide, RewrittenRemote, UrlRewrite};
+use crate::settings::VaqSettings;
 use crate::storage::Storage;
+use crate::sync::{diff_remotes, RemoteChange, RepoRemoteDiff, SyncSide, SyncWriteOptions};
 use crate::tag_filter::TagFilter;
+use crate::template;
 
 use std::collections::BTreeMap;
 use std::convert::Infallible;
@@ -11,6 +23,7 @@ use std::path::{Path, PathBuf};
 
 use derive_more::Display;
 use log::info;
+use serde_derive::{Deserialize, Serialize};
 use thiserror::Error;
 
 // TODO: merge with git.rs?
@@ -20,6 +33,9 @@ use thiserror::Error;
 pub struct Vaquera {
 	storage: Box<dyn Storage>,
 	git: Box<dyn Git>,
+
+	/// Where changes to the config are recorded for `history` and `undo`; `None` records nothing.
+	journal: Option<Box<dyn Storage>>,
 }
 
 #[derive(Debug, Error)]
@@ -45,13 +61,27 @@ enum VaqMainError {
 
 	#[error("IO error")]
 	Io,
+
+	#[error("Failed to write .vaquera.toml. {0}")]
+	Save(io::Error),
+
+	#[error(transparent)]
+	Move(#[from] MoveError),
+
+	#[error(transparent)]
+	Journal(#[from] JournalError),
 }
 
 const ORIGIN: &'static str = "origin";
 
 impl Vaquera {
 	pub fn new(storage: Box<dyn Storage>, git: Box<dyn Git>) -> Self {
-		Self { storage, git }
+		Self { storage, git, journal: None }
+	}
+
+	pub fn with_journal(mut self, journal: Box<dyn Storage>) -> Self {
+		self.journal = Some(journal);
+		self
 	}
 
 	pub fn add(&mut self, repo_path: &Path) -> Result<(), VaqMainError> {
@@ -67,15 +97,44 @@ impl Vaquera {
 
 		repos.add_new_repo(normalized_path.as_path(), remotes)
 			.map_err(VaqMainError::state_error)?;
+		let added = repos.find_by_path(normalized_path.as_path()).map(|repo| repo.clone());
+
+		self.save(repos, &format!("add {}", normalized_path.display()))?;
+
+		if let Some(repo) = added {
+			self.run_hook(HookEvent::PostAdd, &repo);
+		}
 
-		self.save(repos)?;
 		Ok(())
 	}
 
 	pub fn remove_repos_by_name(&mut self, repo_names: &[String]) -> Result<(), VaqMainError> {
 		let mut repos = self.load()?;
 		repos.remove_by_names(repo_names.to_vec());
-		self.save(repos)
+		self.save(repos, &format!("remove {}", repo_names.join(" ")))
+	}
+
+	pub fn remove_repos_by_path(&mut self, repo_paths: &[PathBuf]) -> Result<(), VaqMainError> {
+		let mut repos = self.load()?;
+		repos.remove_by_paths(repo_paths);
+		self.save(repos, &format!("remove {}", display_paths(repo_paths)))
+	}
+
+	/// Removes repos whose folder no longer exists, and with `not_git` also those whose folder
+	/// is not a git repository. Returns the removed repos; with `dry_run` nothing is saved.
+	pub fn prune(&mut self, not_git: bool, dry_run: bool) -> Result<Vec<VaqRepo>, VaqMainError> {
+		let mut repos = self.load()?;
+
+		let pruned = repos.prune(|repo| {
+			!exists(&repo.path) || (not_git && !is_git_repo(&repo.path))
+		});
+
+		if !dry_run && !pruned.is_empty() {
+			let pruned_paths: Vec<PathBuf> = pruned.iter().map(|repo| repo.path.clone()).collect();
+			self.save(repos, &format!("prune {}", display_paths(&pruned_paths)))?;
+		}
+
+		Ok(pruned)
 	}
 
 	pub fn add_tag(
@@ -85,7 +144,7 @@ impl Vaquera {
 	) -> Result<(), VaqMainError> {
 		let mut repos = self.load()?;
 		repos.add_tag(tag_name, repo_names.to_vec())?;
-		self.save(repos)
+		self.save(repos, &format!("tag {} {}", tag_name, repo_names.join(" ")))
 	}
 
 	pub fn remove_tag(
@@ -95,7 +154,7 @@ impl Vaquera {
 	) -> Result<(), VaqMainError> {
 		let mut repos = self.load()?;
 		repos.remove_tag(tag_name, repo_paths.to_vec())?;
-		self.save(repos)
+		self.save(repos, &format!("tag --remove {} {}", tag_name, repo_paths.join(" ")))
 	}
 
 	/// Filter repos by tag filter with AND/OR logic.
@@ -115,7 +174,19 @@ impl Vaquera {
 		self.load()
 	}
 
+	pub fn settings(&self) -> Result<VaqSettings, VaqMainError> {
+		if !self.storage.exists() {
+			return Ok(VaqSettings::default());
+		}
+
+		let state_toml = self.storage.read();
+
+		parse_settings(&state_toml)
+	}
+
+	/// Clones each repo, with the `url_rewrites` rules from the settings applied to its remotes.
 	pub fn clone(&self, repos: Vec<VaqRepo>) -> Result<usize, VaqMainError> {
+		let settings = self.settings()?;
 		let mut error_count = 0;
 
 		for repo in repos {
@@ -128,14 +199,27 @@ impl Vaquera {
 
 			if let Some(clone_remote) = repo.remotes.get(clone_remote_name) {
 				// Clone the repo
-				match self.git.clone(repo.path.as_ref(), clone_remote.url.to_bstring()) {
+				let clone_url = settings.rewrite_url(&clone_remote.url);
+
+				match self.git.clone(repo.path.as_ref(), clone_url.url.to_bstring()) {
 					Ok(()) => {
 						// Add all other remotes
 						for (name, remote) in &repo.remotes {
 							if name != clone_remote_name {
-								self.git.add_remote(repo.path.as_ref(), name, remote.url.to_bstring())?;
+								let url = settings.rewrite_url(&remote.url);
+								self.git.add_remote(repo.path.as_ref(), name, url.url.to_bstring())?;
+							}
+
+							if let Some(push_url) = &remote.push_url {
+								let push_url = settings.rewrite_url(push_url).to_string();
+
+								if let Err(error) = self.git.set_remote_push_url(repo.path.as_ref(), name, Some(&push_url)) {
+									eprintln!("Warning: Could not set push url of remote {} of {}: {}", name, repo.path.display(), error);
+								}
 							}
 						}
+
+						self.run_hook(HookEvent::PostClone, &repo);
 					}
 
 					Err(_) => {
@@ -154,6 +238,19 @@ impl Vaquera {
 		Ok(error_count)
 	}
 
+	/// Commits of all repos matching `filter`, merged into one stream, newest first.
+	pub fn log(&self, filter: &TagFilter, query: &LogQuery) -> Result<VaqLog, VaqMainError> {
+		let repos = self.list(filter)?;
+		Ok(commit_log::log(self.git.as_ref(), repos, query))
+	}
+
+	/// Problems where the config and what is on disk below `workspace_root` disagree.
+	pub fn doctor(&self, workspace_root: &Path) -> Result<Vec<VaqIssue>, VaqMainError> {
+		let repos = self.list(&TagFilter::all())?;
+		let settings = self.settings()?;
+		Ok(doctor::diagnose(self.git.as_ref(), &repos, &settings, workspace_root))
+	}
+
 	pub fn tags(&self) -> Result<VaqTags, VaqMainError> {
 		let repos = self.load()?;
 
@@ -168,29 +265,45 @@ impl Vaquera {
 	}
 
 	pub fn sync_read_remotes(&mut self, filter: &TagFilter) -> Result<(), VaqMainError> {
+		let repo_paths: Vec<PathBuf> = self.list(filter)?
+			.into_iter()
+			.map(|repo| repo.path)
+			.collect();
+
+		self.sync_read_remotes_by_path(&repo_paths)
+	}
+
+	pub fn sync_read_remotes_by_path(&mut self, repo_paths: &[PathBuf]) -> Result<(), VaqMainError> {
 		let mut repos = self.load()?;
-		let repo_list = self.list(filter)?;
+		let settings = self.settings()?;
+		let mut synced = Vec::new();
 		let mut error_count = 0;
 
-		for repo in repo_list {
-			match self.git.read_all_remotes(repo.path.as_ref()) {
+		for repo_path in repo_paths {
+			match self.read_git_remotes(repo_path.as_path()) {
 				Ok(remotes) => {
 					// Find the repo in the mutable repos structure and update its remotes
-					if let Some(repo_mut) = repos.find_by_path(repo.path.as_path()) {
+					if let Some(repo_mut) = repos.find_by_path(repo_path.as_path()) {
+						let remotes = unrewrite_remotes(&settings, &repo_mut.remotes, remotes);
 						repo_mut.replace_remotes(remotes);
+						synced.push(repo_mut.clone());
 
-						info!("Updated {} with remotes from git", repo.path.display());
+						info!("Updated {} with remotes from git", repo_path.display());
 					}
 				}
 
 				Err(_) => {
-					eprintln!("Warning: Could not read remotes from {}", repo.path.display());
+					eprintln!("Warning: Could not read remotes from {}", repo_path.display());
 					error_count += 1;
 				}
 			}
 		}
 
-		self.save(repos)?;
+		self.save(repos, "sync --read-remotes")?;
+
+		for repo in &synced {
+			self.run_hook(HookEvent::PostSync, repo);
+		}
 
 		if error_count > 0 {
 			eprintln!("{error_count} repos failed to sync");
@@ -200,15 +313,54 @@ impl Vaquera {
 		Ok(())
 	}
 
-	pub fn sync_write_remotes(&self, filter: &TagFilter) -> Result<(), VaqMainError> {
+	/// What `sync --write-remotes --prune --update-urls` would change in git, per repo.
+	/// Repos already in sync are omitted.
+	pub fn sync_diff(&self, filter: &TagFilter) -> Result<Vec<RepoRemoteDiff>, VaqMainError> {
 		let repo_list = self.list(filter)?;
+		let settings = self.settings()?;
+		let mut diffs = Vec::new();
 		let mut error_count = 0;
 
 		for repo in repo_list {
+			match self.remote_changes(&repo, &settings) {
+				Ok(changes) if changes.is_empty() => {}
+				Ok(changes) => diffs.push(RepoRemoteDiff { path: repo.path, changes }),
+
+				Err(_) => {
+					eprintln!("Warning: Could not read remotes from {}", repo.path.display());
+					error_count += 1;
+				}
+			}
+		}
+
+		if error_count > 0 {
+			eprintln!("{error_count} repos failed to sync");
+			std::process::exit(1);
+		}
+
+		Ok(diffs)
+	}
+
+	/// Makes git remotes match the config. Missing remotes are always added; stale remotes
+	/// are only removed with `prune`, and differing URLs only overwritten with `update_urls`.
+	/// When `resolve` is given it is asked which side wins for every differing URL, and the
+	/// config is updated when git wins.
+	pub fn sync_write_remotes(
+		&mut self,
+		filter: &TagFilter,
+		options: &SyncWriteOptions,
+		resolve: Option<&dyn Fn(&Path, &RemoteChange) -> SyncSide>,
+	) -> Result<(), VaqMainError> {
+		let mut repos = self.load()?;
+		let repo_list = self.list(filter)?;
+		let settings = self.settings()?;
+		let mut config_changed = false;
+		let mut synced = Vec::new();
+		let mut error_count = 0;
 
-			// Get current remotes from git
-			let current_remotes = match self.git.read_all_remotes(repo.path.clone()) {
-				Ok(remotes) => remotes,
+		for repo in repo_list {
+			let changes = match self.remote_changes(&repo, &settings) {
+				Ok(changes) => changes,
 
 				Err(_) => {
 					eprintln!("Warning: Could not write remotes to {}", repo.path.display());
@@ -217,13 +369,114 @@ impl Vaquera {
 				}
 			};
 
-			// Add any missing remotes from config
-			for (name, remote) in &repo.remotes {
-				if !current_remotes.contains_key(name) {
-					self.git.add_remote(repo.path.as_ref(), name.as_ref(), remote.url.to_bstring())?;
-					info!("Added remote {} to {}", name.as_ref(), repo.path.display());
+			let mut failed = false;
+
+			for change in changes {
+				let is_conflict = matches!(change, RemoteChange::Changed { .. } | RemoteChange::PushUrlDiffers { .. });
+
+				let side = match resolve {
+					Some(resolve) if is_conflict => resolve(repo.path.as_path(), &change),
+					_ => SyncSide::Config,
+				};
+
+				let update_urls = options.update_urls || resolve.is_some();
+				let path = repo.path.as_path();
+
+				let result = match (side, &change) {
+					(SyncSide::Config, RemoteChange::Added { name, url }) => {
+						let push_url = repo.remotes.get(name)
+							.and_then(|remote| remote.push_url.as_ref())
+							.map(|url| settings.rewrite_url(url).to_string());
+
+						let added = self.git.add_remote(path, name, &VaqUrl::new(url));
+
+						match push_url {
+							Some(push_url) if added.is_ok() => self.git.set_remote_push_url(path, name, Some(&push_url)),
+							_ => added,
+						}
+					}
+
+					(SyncSide::Config, RemoteChange::Removed { name, .. }) if options.prune => {
+						self.git.remove_remote(path, name)
+					}
+
+					(SyncSide::Config, RemoteChange::Changed { name, config_url, .. }) if update_urls => {
+						self.git.set_remote_url(path, name, config_url)
+					}
+
+					(SyncSide::Config, RemoteChange::PushUrlDiffers { name, .. }) if update_urls => {
+						let push_url = repo.remotes.get(name)
+							.and_then(|remote| remote.push_url.as_ref())
+							.map(|url| settings.rewrite_url(url).to_string());
+
+						self.git.set_remote_push_url(path, name, push_url.as_deref())
+					}
+
+					(SyncSide::Git, RemoteChange::Changed { name, git_url, .. }) => {
+						let url = VaqUrlBuf::try_from(git_url.as_str())
+							.map_err(|e| VaqMainError::state_error_invalid_url(e, git_url))?;
+
+						if let Some(repo_mut) = repos.find_by_path(path) {
+							let push_url = repo_mut.remotes.get(name).and_then(|remote| remote.push_url.clone());
+							repo_mut.add_remote(VaqRemote::new(name.clone(), url).with_push_url(push_url));
+							config_changed = true;
+							info!("Updated remote {} of {} in config", name, path.display());
+						}
+						continue;
+					}
+
+					(SyncSide::Git, RemoteChange::PushUrlDiffers { name, push_url, .. }) => {
+						let url = VaqUrlBuf::try_from(push_url.as_str())
+							.map_err(|e| VaqMainError::state_error_invalid_url(e, push_url))?;
+
+						if let Some(repo_mut) = repos.find_by_path(path) {
+							if let Some(remote) = repo_mut.remotes.get(name).cloned() {
+								// Pushing to the remote URL needs no separate push URL
+								let push_url = (remote.url.to_string() != *push_url).then_some(url);
+
+								repo_mut.add_remote(remote.with_push_url(push_url));
+								config_changed = true;
+								info!("Updated push url of remote {} of {} in config", name, path.display());
+							}
+						}
+						continue;
+					}
+
+					_ => continue,
+				};
+
+				match result {
+					Ok(()) => match &change {
+						RemoteChange::Added { name, .. } => info!("Added remote {} to {}", name, path.display()),
+						RemoteChange::Removed { name, .. } => info!("Removed remote {} from {}", name, path.display()),
+						_ => info!("Updated url of remote {} in {}", change.name(), path.display()),
+					},
+
+					Err(error) => {
+						eprintln!("Warning: Could not update remote {} of {}: {}", change.name(), path.display(), error);
+						failed = true;
+					}
 				}
 			}
+
+			if failed {
+				error_count += 1;
+			} else {
+				synced.push(repo.path);
+			}
+		}
+
+		// Hooks get the repos as they are after syncing, with remotes taken from git
+		let synced: Vec<VaqRepo> = synced.iter()
+			.filter_map(|path| repos.find_by_path(path).map(|repo| repo.clone()))
+			.collect();
+
+		if config_changed {
+			self.save(repos, "sync --write-remotes")?;
+		}
+
+		for repo in &synced {
+			self.run_hook(HookEvent::PostSync, repo);
 		}
 
 		if error_count > 0 {
@@ -234,6 +487,352 @@ impl Vaquera {
 		Ok(())
 	}
 
+	/// Applies `edit` to the repos matching `filter`, in git where the repo folder exists and in
+	/// the config. A repo where the edit doesn't apply (e.g. adding a remote that already exists)
+	/// is reported and left unchanged.
+	pub fn edit_remotes(&mut self, filter: &TagFilter, edit: &RemoteEdit) -> Result<(), VaqMainError> {
+		let mut repos = self.load()?;
+		let repo_list = self.list(filter)?;
+		let mut error_count = 0;
+
+		for repo in repo_list {
+			match self.edit_repo_remotes(&repo, edit) {
+				Ok(edited) => {
+					if let Some(repo_mut) = repos.find_by_path(repo.path.as_path()) {
+						*repo_mut = edited;
+					}
+				}
+
+				Err(message) => {
+					eprintln!("Warning: {}: {}", repo.path.display(), message);
+					error_count += 1;
+				}
+			}
+		}
+
+		self.save(repos, &format!("remote {edit}"))?;
+
+		if error_count > 0 {
+			eprintln!("{error_count} repos failed to update remotes");
+			std::process::exit(1);
+		}
+
+		Ok(())
+	}
+
+	/// Turns each repo matching `filter` into a fork checkout: the canonical `origin` is renamed
+	/// to `upstream`, `owner`'s fork is added as `origin`, and the current branch tracks
+	/// `upstream`. Repos already set up this way are left alone.
+	pub fn fork_setup(&mut self, filter: &TagFilter, owner: &str) -> Result<(), VaqMainError> {
+		let mut repos = self.load()?;
+		let repo_list = self.list(filter)?;
+		let mut error_count = 0;
+
+		for repo in repo_list {
+			match self.fork_repo(&repo, owner) {
+				Ok(Some(forked)) => {
+					if let Some(repo_mut) = repos.find_by_path(repo.path.as_path()) {
+						*repo_mut = forked;
+					}
+				}
+
+				Ok(None) => info!("{} already set up as fork of {}", repo.path.display(), owner),
+
+				Err(message) => {
+					eprintln!("Warning: {}: {}", repo.path.display(), message);
+					error_count += 1;
+				}
+			}
+		}
+
+		self.save(repos, &format!("fork setup --owner {owner}"))?;
+
+		if error_count > 0 {
+			eprintln!("{error_count} repos failed to set up as fork");
+			std::process::exit(1);
+		}
+
+		Ok(())
+	}
+
+	/// `repo` with its fork remotes set up, or `None` if nothing needed to change.
+	fn fork_repo(&self, repo: &VaqRepo, owner: &str) -> Result<Option<VaqRepo>, String> {
+		let origin = repo.remotes.get(ORIGIN).ok_or_else(|| format!("no {ORIGIN} remote"))?;
+
+		if let Some(upstream) = repo.remotes.get(UPSTREAM) {
+			let expected_origin = fork::fork_url(&upstream.url, owner).map(|url| url.to_string());
+
+			return if expected_origin == Some(origin.url.to_string()) {
+				Ok(None)
+			} else {
+				Err(format!("{UPSTREAM} remote already exists"))
+			};
+		}
+
+		let fork_url = fork::fork_url(&origin.url, owner)
+			.ok_or_else(|| format!("cannot derive fork URL from {}", origin.url))?;
+
+		if fork_url.to_string() == origin.url.to_string() {
+			return Ok(None);
+		}
+
+		let renamed = self.edit_repo_remotes(repo, &RemoteEdit::Rename {
+			name: ORIGIN.to_string(),
+			new_name: UPSTREAM.to_string(),
+		})?;
+
+		let add_fork = RemoteEdit::Add { name: ORIGIN.to_string(), url: fork_url.to_string() };
+		let forked = match self.edit_repo_remotes(&renamed, &add_fork) {
+			Ok(forked) => forked,
+
+			Err(message) => {
+				// Put the canonical remote back so the repo is left as it was
+				self.edit_repo_remotes(&renamed, &RemoteEdit::Rename {
+					name: UPSTREAM.to_string(),
+					new_name: ORIGIN.to_string(),
+				})?;
+				return Err(message);
+			}
+		};
+
+		if exists(&repo.path) {
+			let path = repo.path.as_path();
+			let tracked = self.git.current_branch(path).and_then(|branch| {
+				self.git.set_branch_upstream(path, &branch, &format!("{UPSTREAM}/{branch}"))?;
+				Ok(branch)
+			});
+
+			match tracked {
+				Ok(branch) => info!("{} now tracks {}/{} in {}", branch, UPSTREAM, branch, path.display()),
+				Err(error) => eprintln!("Warning: Could not set tracking branch in {}: {}", path.display(), error),
+			}
+		}
+
+		Ok(Some(forked))
+	}
+
+	/// Applies `edit` in git, then returns `repo` with it applied to its config.
+	fn edit_repo_remotes(&self, repo: &VaqRepo, edit: &RemoteEdit) -> Result<VaqRepo, String> {
+		let path = repo.path.as_path();
+
+		let git_remotes = if exists(&repo.path) {
+			Some(self.git.read_all_remotes(path).map_err(|e| e.to_string())?)
+		} else {
+			None
+		};
+
+		let in_git = |name: &str| git_remotes.as_ref().is_some_and(|remotes| remotes.contains(name));
+		let known = |name: &str| repo.remotes.contains(name) || in_git(name);
+		let expand_url = |url: &str| {
+			let url = template::expand(url, repo);
+			VaqUrlBuf::try_from(url.as_str()).map_err(|e| e.to_string())
+		};
+
+		let mut edited = repo.clone();
+
+		match edit {
+			RemoteEdit::Add { name, url } => {
+				if known(name) {
+					return Err(format!("remote {name} already exists"));
+				}
+
+				let url = expand_url(url)?;
+				if git_remotes.is_some() {
+					self.git.add_remote(path, name, &VaqUrl::new(&url.to_string())).map_err(|e| e.to_string())?;
+				}
+
+				edited.add_remote(VaqRemote::new(name.clone(), url));
+				info!("Added remote {} to {}", name, path.display());
+			}
+
+			RemoteEdit::Rename { name, new_name } => {
+				if !known(name) {
+					return Err(format!("no remote {name}"));
+				}
+				if known(new_name) {
+					return Err(format!("remote {new_name} already exists"));
+				}
+
+				if in_git(name) {
+					self.git.rename_remote(path, name, new_name).map_err(|e| e.to_string())?;
+				}
+
+				if let Some(remote) = edited.remove_remote(name) {
+					edited.add_remote(VaqRemote::new(new_name.clone(), remote.url).with_push_url(remote.push_url));
+				}
+				info!("Renamed remote {} to {} in {}", name, new_name, path.display());
+			}
+
+			RemoteEdit::Remove { name } => {
+				if !known(name) {
+					return Err(format!("no remote {name}"));
+				}
+
+				if in_git(name) {
+					self.git.remove_remote(path, name).map_err(|e| e.to_string())?;
+				}
+
+				edited.remove_remote(name);
+				info!("Removed remote {} from {}", name, path.display());
+			}
+
+			RemoteEdit::SetUrl { name, url } => {
+				if !known(name) {
+					return Err(format!("no remote {name}"));
+				}
+
+				let url = expand_url(url)?;
+				let result = if in_git(name) {
+					self.git.set_remote_url(path, name, &url.to_string())
+				} else if git_remotes.is_some() {
+					self.git.add_remote(path, name, &VaqUrl::new(&url.to_string()))
+				} else {
+					Ok(())
+				};
+				result.map_err(|e| e.to_string())?;
+
+				let push_url = edited.remotes.get(name).and_then(|remote| remote.push_url.clone());
+				edited.add_remote(VaqRemote::new(name.clone(), url).with_push_url(push_url));
+				info!("Updated url of remote {} in {}", name, path.display());
+			}
+		}
+
+		Ok(edited)
+	}
+
+	/// Rewrites remote URLs starting with `rule.from` in repos matching `filter`, both in the
+	/// config and in git (fetch and push URLs). Returns what was rewritten; with `dry_run`
+	/// nothing is changed.
+	pub fn rewrite_remotes(
+		&mut self,
+		filter: &TagFilter,
+		rule: &UrlRewrite,
+		dry_run: bool,
+	) -> Result<Vec<RewrittenRemote>, VaqMainError> {
+		let mut repos = self.load()?;
+		let repo_list = self.list(filter)?;
+		let mut rewritten = Vec::new();
+		let mut error_count = 0;
+
+		for repo in repo_list {
+			let path = repo.path.as_path();
+
+			for (name, remote) in &repo.remotes.items {
+				let new_url = rule.apply(&remote.url);
+				let new_push_url = remote.push_url.as_ref().and_then(|push_url| rule.apply(push_url));
+
+				if new_url.is_none() && new_push_url.is_none() {
+					continue;
+				}
+
+				let config_urls = [
+					(RewriteSide::Config, Some(&remote.url), new_url.as_ref()),
+					(RewriteSide::ConfigPush, remote.push_url.as_ref(), new_push_url.as_ref()),
+				];
+
+				for (side, old_url, new_url) in config_urls {
+					if let (Some(old_url), Some(new_url)) = (old_url, new_url) {
+						rewritten.push(RewrittenRemote {
+							path: repo.path.clone(),
+							name: name.to_string(),
+							side,
+							old_url: old_url.to_string(),
+							new_url: new_url.to_string(),
+						});
+					}
+				}
+
+				if !dry_run {
+					if let Some(repo_mut) = repos.find_by_path(path) {
+						let url = new_url.unwrap_or_else(|| remote.url.clone());
+						let push_url = new_push_url.or_else(|| remote.push_url.clone());
+						repo_mut.add_remote(VaqRemote::new(name.to_string(), url).with_push_url(push_url));
+					}
+				}
+			}
+
+			if !exists(&repo.path) {
+				continue;
+			}
+
+			let git_urls = match (self.git.read_all_remotes(path), self.git.read_push_urls(path)) {
+				(Ok(remotes), Ok(push_urls)) => {
+					let fetch_urls = remotes.into_iter().map(|remote| (RewriteSide::Git, remote.name.to_string(), remote.url.to_string()));
+					let push_urls = push_urls.into_iter().map(|(name, url)| (RewriteSide::GitPush, name, url));
+					fetch_urls.chain(push_urls).collect::<Vec<_>>()
+				}
+
+				_ => {
+					eprintln!("Warning: Could not read remotes from {}", path.display());
+					error_count += 1;
+					continue;
+				}
+			};
+
+			for (side, name, old_url) in git_urls {
+				let Some(new_url) = rule.apply_str(&old_url) else {
+					continue;
+				};
+				let new_url = new_url.to_string();
+
+				if !dry_run {
+					let result = match side {
+						RewriteSide::GitPush => self.git.set_remote_push_url(path, &name, Some(&new_url)),
+						_ => self.git.set_remote_url(path, &name, &new_url),
+					};
+
+					if let Err(error) = result {
+						eprintln!("Warning: Could not update remote {} of {}: {}", name, path.display(), error);
+						error_count += 1;
+						continue;
+					}
+
+					info!("Updated url of remote {} in {}", name, path.display());
+				}
+
+				rewritten.push(RewrittenRemote { path: repo.path.clone(), name, side, old_url, new_url });
+			}
+		}
+
+		let config_changed = rewritten.iter()
+			.any(|remote| matches!(remote.side, RewriteSide::Config | RewriteSide::ConfigPush));
+		if !dry_run && config_changed {
+			self.save(repos, &format!("remotes rewrite --from {} --to {}", rule.from, rule.to))?;
+		}
+
+		if error_count > 0 {
+			eprintln!("{error_count} repos failed to rewrite");
+			std::process::exit(1);
+		}
+
+		Ok(rewritten)
+	}
+
+	/// The remotes of the repo at `path` in git, with their separate push URLs.
+	fn read_git_remotes(&self, path: &Path) -> Result<VaqRemotes, String> {
+		let mut remotes = self.git.read_all_remotes(path).map_err(|e| e.to_string())?;
+		let push_urls = self.git.read_push_urls(path).map_err(|e| e.to_string())?;
+
+		for (name, push_url) in push_urls {
+			let push_url = VaqUrlBuf::try_from(push_url.as_str()).map_err(|e| e.to_string())?;
+
+			if let Some(remote) = remotes.items.get_mut(&name) {
+				*remote = remote.clone().with_push_url(Some(push_url));
+			}
+		}
+
+		Ok(remotes)
+	}
+
+	/// Differences between the remotes of `repo` in git and in the config, with the config URLs
+	/// rewritten as they were when cloning.
+	fn remote_changes(&self, repo: &VaqRepo, settings: &VaqSettings) -> Result<Vec<RemoteChange>, GitError> {
+		let git_remotes = self.git.read_all_remotes(repo.path.as_path())?;
+		let push_urls = self.git.read_push_urls(repo.path.as_path())?;
+
+		Ok(diff_remotes(&settings.rewrite_remotes(&repo.remotes), &git_remotes, &push_urls))
+	}
+
 	fn show_by_name(&self, repo_name: &str) -> Result<&mut VaqRepo, VaqMainError> {
 		let mut repos = self.load()?;
 		let repo = repos.find_by_name(repo_name)
@@ -260,16 +859,21 @@ impl Vaquera {
 		target_path: P,
 		tags: T,
 	) -> Result<String, VaqMainError> {
-		// Use target_path if provided, otherwise extract from URL
-		let path_name = match target_path {
-			Some(path) => path.to_string(),
-			None => extract_repo_name_from_url(url).ok_or_else(|| StateError {
+		// Use target_path if provided, otherwise the configured layout, otherwise extract from URL
+		let layout_path = self.settings()?.layout
+			.and_then(|layout| template::expand_layout(&layout, &VaqUrlBuf::new(url.as_ref())));
+
+		let path_name = match (target_path, layout_path) {
+			(Some(path), _) => path.to_string(),
+			(None, Some(path)) => path.to_string_lossy().to_string(),
+			(None, None) => extract_repo_name_from_url(url).ok_or_else(|| StateError {
 				message: format!("Could not extract repository name from URL: {}", url),
 			})?,
 		};
 
-		// Clone the repository
-		self.git.clone(&path_name, url)?;
+		// Clone the repository, with the `url_rewrites` rules applied
+		let clone_url = self.settings()?.rewrite_url(&VaqUrlBuf::new(url.as_ref()));
+		self.git.clone(&path_name, clone_url.url.to_bstring())?;
 
 		// Add the repository to vaquera
 		self.add(path_name.clone())?;
@@ -281,45 +885,272 @@ impl Vaquera {
 			}
 		}
 
+		if let Ok(repo) = find_repo(&self.load()?, &path_name) {
+			self.run_hook(HookEvent::PostClone, &repo);
+		}
+
 		Ok(path_name)
 	}
 
-	pub fn move_repo(&mut self, old_path: &str, new_path: &str) -> Result<(), VaqMainError> {
+	/// Moves repos like `mv`: a single repo is renamed to `destination` unless that is an existing
+	/// folder, otherwise every repo is moved into `destination`, which is created if needed.
+	/// Repos are given by path or name. If one of several repos can't be moved, the ones already
+	/// moved are moved back. Returns the (old, new) path of every repo moved.
+	pub fn move_repos(&mut self, repo_ids: &[String], destination: &Path) -> Result<Vec<(PathBuf, PathBuf)>, VaqMainError> {
+		let destination = normalize_path(destination);
+		let into_folder = repo_ids.len() > 1 || (destination.is_dir() && !is_git_repo(&destination));
+
+		self.move_repos_to(repo_ids, &destination, into_folder)
+	}
+
+	/// Moves repos into `folder`, which is created if needed, even when there is only one.
+	pub fn move_repos_into(&mut self, repo_ids: &[String], folder: &Path) -> Result<Vec<(PathBuf, PathBuf)>, VaqMainError> {
+		self.move_repos_to(repo_ids, &normalize_path(folder), true)
+	}
+
+	fn move_repos_to(
+		&mut self,
+		repo_ids: &[String],
+		destination: &Path,
+		into_folder: bool,
+	) -> Result<Vec<(PathBuf, PathBuf)>, VaqMainError> {
+		let mut moved = Vec::new();
+
+		for repo_id in repo_ids {
+			let result = self.target_path(repo_id, destination, into_folder)
+				.and_then(|new_path| Ok((self.move_repo(repo_id, &new_path)?, new_path)));
+
+			match result {
+				Ok(paths) => moved.push(paths),
+
+				Err(error) => {
+					self.move_back(&moved);
+					return Err(error);
+				}
+			}
+		}
+
+		Ok(moved)
+	}
+
+	fn target_path(&self, repo_id: &str, destination: &Path, into_folder: bool) -> Result<PathBuf, VaqMainError> {
+		if !into_folder {
+			return Ok(destination.to_path_buf());
+		}
+
+		let old_path = find_repo(&self.load()?, repo_id)?.path;
+		let folder_name = old_path.file_name().ok_or_else(|| MoveError::NotFound(repo_id.to_string()))?;
+		Ok(destination.join(folder_name))
+	}
+
+	/// Undoes the `moved` (old, new) moves of `move_repos`, most recent first.
+	fn move_back(&mut self, moved: &[(PathBuf, PathBuf)]) {
+		for (old_path, new_path) in moved.iter().rev() {
+			match self.move_repo(&new_path.to_string_lossy(), old_path) {
+				Ok(_) => info!("Moved {} back to {}", new_path.display(), old_path.display()),
+				Err(error) => eprintln!(
+					"Warning: Could not move {} back to {}: {}",
+					new_path.display(),
+					old_path.display(),
+					error
+				),
+			}
+		}
+	}
+
+	/// Moves a repo, given by path or name, to `new_path` and updates the config. Repos not
+	/// cloned yet only get their path changed. Across filesystems the folder is copied and
+	/// verified before the original is deleted, and worktree links are fixed afterwards. If the
+	/// config can't be saved, the folder is moved back. Returns the old path.
+	pub fn move_repo(&mut self, repo_id: &str, new_path: &Path) -> Result<PathBuf, VaqMainError> {
 		let mut repos = self.load()?;
-		let normalized_old = normalize_path(old_path.to_string());
-		let normalized_new = normalize_path(new_path.to_string());
+		let old_path = find_repo(&repos, repo_id)?.path;
+		let new_path = normalize_path(new_path);
 
-		// Find the repo in the config
-		let repo = repos
-			.as_vec()
-			.iter()
-			.find(|r| r.path == normalized_old)
-			.ok_or_else(|| StateError {
-				message: format!("Repo '{}' not found", normalized_old),
-			})?
-			.clone();
-
-		// Create parent paths if they don't exist
-		if let Some(parent) = std::path::Path::new(&normalized_new).parent() {
-			if !parent.as_os_str().is_empty() {
-				std::fs::create_dir_all(parent).map_err(|e| IoError { inner: e })?;
+		if exists(&new_path) || repos.index_by_path(&new_path).is_some() {
+			return Err(MoveError::TargetExists(new_path).into());
+		}
+
+		let on_disk = exists(&old_path);
+
+		if on_disk {
+			relocate::move_dir(&old_path, &new_path)?;
+
+			if let Err(error) = relocate::fix_worktree_links(&new_path) {
+				eprintln!("Warning: Could not update worktree links of {}: {}", new_path.display(), error);
 			}
 		}
 
-		// Move the actual path on the filesystem
-		std::fs::rename(&normalized_old, &normalized_new).map_err(|e| IoError { inner: e })?;
+		repos.set_path(&old_path, &new_path);
+		let moved = find_repo(&repos, &new_path.to_string_lossy()).ok();
 
-		// Update the config: remove old entry and add new one with same tags/remotes
-		repos.remove_by_names(vec![normalized_old]);
-		repos.add_with_tags_and_remotes(normalized_new, repo.tags, repo.remotes);
+		let moves = if on_disk {
+			vec![FolderMove { from: old_path.clone(), to: new_path.clone() }]
+		} else {
+			Vec::new()
+		};
+		let action = format!("move {} {}", old_path.display(), new_path.display());
 
-		self.save(repos)?;
-		Ok(())
+		if let Err(error) = self.save_with_moves(repos, &action, moves) {
+			if on_disk {
+				match relocate::move_dir(&new_path, &old_path) {
+					Ok(()) => {
+						let _ = relocate::fix_worktree_links(&old_path);
+					}
+					Err(rollback_error) => eprintln!(
+						"Warning: Could not move {} back to {}: {}",
+						new_path.display(),
+						old_path.display(),
+						rollback_error
+					),
+				}
+			}
+
+			return Err(error);
+		}
+
+		if let Some(repo) = moved.filter(|_| on_disk) {
+			self.run_hook(HookEvent::PostMove, &repo);
+		}
+
+		Ok(old_path)
+	}
+
+	/// Moves every repo to where `layout` puts it, based on the URL of its `origin` remote (or
+	/// its first remote). Repos not cloned yet only get their path changed in the config.
+	/// Targets that already exist or that several repos expand to are returned as
+	/// `MoveError::LayoutCollisions` up front, also with `dry_run`, and then nothing is moved.
+	/// If a move fails, the repos already moved are moved back. Returns the (old, new) paths of
+	/// the repos moved; with `dry_run` nothing is changed.
+	pub fn relayout(&mut self, layout: &str, dry_run: bool) -> Result<Vec<(PathBuf, PathBuf)>, VaqMainError> {
+		let repo_list = self.list(&TagFilter::all())?;
+		let mut planned: Vec<(PathBuf, PathBuf)> = Vec::new();
+
+		for repo in repo_list {
+			let remote = repo.remotes.get(ORIGIN).or_else(|| repo.remotes.items.values().next());
+
+			let Some(new_path) = remote.and_then(|remote| template::expand_layout(layout, &remote.url)) else {
+				info!("No layout path for {}, skipped", repo.path.display());
+				continue;
+			};
+
+			if new_path != repo.path {
+				planned.push((repo.path, new_path));
+			}
+		}
+
+		let mut targets: BTreeMap<&Path, Vec<&Path>> = BTreeMap::new();
+		for (old_path, new_path) in &planned {
+			targets.entry(new_path.as_path()).or_default().push(old_path.as_path());
+		}
+
+		let mut collisions = Vec::new();
+
+		for (new_path, old_paths) in &targets {
+			if old_paths.len() > 1 {
+				collisions.push(LayoutCollision::SameTarget {
+					old_paths: old_paths.iter().map(|path| path.to_path_buf()).collect(),
+					new_path: new_path.to_path_buf(),
+				});
+			} else if exists(new_path) {
+				collisions.push(LayoutCollision::TargetExists {
+					old_path: old_paths[0].to_path_buf(),
+					new_path: new_path.to_path_buf(),
+				});
+			}
+		}
+
+		if !collisions.is_empty() {
+			return Err(MoveError::LayoutCollisions(collisions).into());
+		}
+
+		if !dry_run {
+			let mut moved = Vec::new();
+
+			for (old_path, new_path) in &planned {
+				if let Err(error) = self.move_repo(&old_path.to_string_lossy(), new_path) {
+					self.move_back(&moved);
+					return Err(error);
+				}
+				moved.push((old_path.clone(), new_path.clone()));
+			}
+		}
+
+		Ok(planned)
+	}
+
+	/// Recorded changes to the config, most recent first.
+	pub fn history(&self) -> Result<Vec<JournalEntry>, VaqMainError> {
+		let journal_storage = self.journal.as_ref().ok_or(JournalError::Disabled)?;
+		let journal = Journal::load(journal_storage.as_ref())?;
+
+		Ok(journal.entries().cloned().collect())
+	}
+
+	/// Reverts the `count` most recent changes to the config, moving repo folders back where a
+	/// change moved them. Returns the changes undone, most recent first.
+	pub fn undo(&mut self, count: usize) -> Result<Vec<JournalEntry>, VaqMainError> {
+		let journal_storage = self.journal.as_ref().ok_or(JournalError::Disabled)?;
+		let mut journal = Journal::load(journal_storage.as_ref())?;
+
+		let current = if self.storage.exists() { self.storage.read() } else { String::new() };
+		let undone = journal.pop(count, &current)?;
+
+		for folder_move in undone.iter().flat_map(|entry| entry.moves.iter().rev()) {
+			if exists(&folder_move.to) && !exists(&folder_move.from) {
+				relocate::move_dir(&folder_move.to, &folder_move.from)?;
+
+				if let Err(error) = relocate::fix_worktree_links(&folder_move.from) {
+					eprintln!("Warning: Could not update worktree links of {}: {}", folder_move.from.display(), error);
+				}
+			}
+		}
+
+		let restored = &undone.last().expect("pop returns at least one entry").before;
+
+		if restored.is_empty() {
+			self.storage.remove();
+		} else {
+			self.storage.save(restored.clone()).map_err(VaqMainError::Save)?;
+		}
+
+		journal.save(journal_storage.as_ref())?;
+		Ok(undone)
+	}
+
+	/// Runs the hook configured for `event` in `repo`, if any. Hooks follow a change that is
+	/// already done, so a failing hook is only a warning.
+	fn run_hook(&self, event: HookEvent, repo: &VaqRepo) {
+		let hooks = self.settings().map(|settings| settings.hooks).unwrap_or_default();
+
+		if let Err(error) = hooks::run(&hooks, event, repo, &BTreeMap::new()) {
+			eprintln!("Warning: {error}");
+		}
 	}
 
-	fn save(&self, repos: VaqRepos) -> Result<(), VaqMainError> {
-		let state_toml = serialize(&repos)?;
-		self.storage.save(state_toml);
+	/// Saves the config and records the change in the journal as `action`.
+	fn save(&self, repos: VaqRepos, action: &str) -> Result<(), VaqMainError> {
+		self.save_with_moves(repos, action, Vec::new())
+	}
+
+	fn save_with_moves(&self, repos: VaqRepos, action: &str, moves: Vec<FolderMove>) -> Result<(), VaqMainError> {
+		let settings = self.settings()?;
+		let state_toml = serialize(&repos, &settings)?;
+		let before = if self.storage.exists() { self.storage.read() } else { String::new() };
+
+		self.storage.save(state_toml.clone()).map_err(VaqMainError::Save)?;
+
+		if let Some(journal_storage) = &self.journal {
+			if before != state_toml {
+				let entry = JournalEntry::new(action, before, state_toml, moves);
+
+				// The change itself succeeded; failing to record it shouldn't undo that
+				if let Err(error) = journal::append(journal_storage.as_ref(), entry) {
+					eprintln!("Warning: Could not record change in history: {}", error);
+				}
+			}
+		}
+
 		Ok(())
 	}
 
@@ -334,15 +1165,36 @@ impl Vaquera {
 	}
 }
 
-fn serialize(repos: &VaqRepos) -> Result<String, VaqMainError> {
-	toml::to_string(&repos).map_err(|error| StateError {
+/// `.vaquera.toml` as read: the settings tables next to the `repos` list.
+#[derive(Deserialize)]
+struct VaqStateToml {
+	#[serde(flatten)]
+	#[allow(dead_code)]
+	settings: VaqSettings,
+
+	#[serde(flatten)]
+	named_container: BTreeMap<String, Vec<VaqRepo>>,
+}
+
+/// `.vaquera.toml` as written; borrows so saving doesn't need to clone the repo list.
+#[derive(Serialize)]
+struct VaqStateTomlRef<'a> {
+	#[serde(flatten)]
+	settings: &'a VaqSettings,
+	repos: &'a [VaqRepo],
+}
+
+fn serialize(repos: &VaqRepos, settings: &VaqSettings) -> Result<String, VaqMainError> {
+	let state = VaqStateTomlRef { settings, repos: repos.as_vec() };
+
+	toml::to_string(&state).map_err(|error| StateError {
 		message: format!("Failed to generate toml for repo list. {error}"),
 	})
 }
 
 // TODO: should not work
 fn parse(state_toml: &str) -> Result<VaqRepos, VaqMainError> {
-	let mut named_container: BTreeMap<String, Vec<VaqRepo>> =
+	let VaqStateToml { mut named_container, .. } =
 		toml::from_str(state_toml).map_err(|error| VaqMainError::state {
 			message: format!("Failed to parse state data as valid TOML. {error}"),
 		})?;
@@ -353,6 +1205,57 @@ fn parse(state_toml: &str) -> Result<VaqRepos, VaqMainError> {
 	Ok(VaqRepos::new_with_repos(repos))
 }
 
+/// `git_remotes`, with URLs that are the `url_rewrites` result of the configured URL put back
+/// to the configured one, so reading remotes doesn't replace the config URLs with their rewrites.
+fn unrewrite_remotes(settings: &VaqSettings, configured: &VaqRemotes, git_remotes: VaqRemotes) -> VaqRemotes {
+	let mut remotes = git_remotes;
+
+	for (name, remote) in remotes.items.iter_mut() {
+		let Some(configured) = configured.items.get(name) else {
+			continue;
+		};
+
+		if settings.rewrite_url(&configured.url).to_string() == remote.url.to_string() {
+			remote.url = configured.url.clone();
+		}
+
+		if let (Some(configured_push_url), Some(push_url)) = (&configured.push_url, &remote.push_url) {
+			if settings.rewrite_url(configured_push_url).to_string() == push_url.to_string() {
+				remote.push_url = Some(configured_push_url.clone());
+			}
+		}
+	}
+
+	remotes
+}
+
+pub(crate) fn display_paths(paths: &[PathBuf]) -> String {
+	paths.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// The repo at path `repo_id`, or else the only repo named `repo_id`.
+fn find_repo(repos: &VaqRepos, repo_id: &str) -> Result<VaqRepo, MoveError> {
+	let path = normalize_path(Path::new(repo_id));
+
+	if let Some(repo) = repos.as_vec().iter().find(|repo| repo.path == path) {
+		return Ok(repo.clone());
+	}
+
+	let mut named = repos.as_vec().iter().filter(|repo| repo.name == repo_id);
+
+	match (named.next(), named.next()) {
+		(Some(repo), None) => Ok(repo.clone()),
+		(Some(_), Some(_)) => Err(MoveError::Ambiguous(repo_id.to_string())),
+		(None, _) => Err(MoveError::NotFound(repo_id.to_string())),
+	}
+}
+
+fn parse_settings(state_toml: &str) -> Result<VaqSettings, VaqMainError> {
+	toml::from_str(state_toml).map_err(|error| VaqMainError::state {
+		message: format!("Failed to parse settings in state TOML. {error}"),
+	})
+}
+
 fn normalize_paths(repo_paths: &[PathBuf]) -> Vec<PathBuf> {
 	repo_paths
 		.iter()
@@ -1,4 +1,4 @@
-use std::ops::Deref;
+use std::ops::{Deref, DerefMut};
 use std::path::PathBuf;
 use bstr::{BStr, BString, ByteSlice};
 use derive_more::Display;
@@ -10,6 +10,44 @@ pub struct VaqTagsBuf {
 	items: Vec<String>
 }
 
+impl Deref for VaqTagsBuf {
+	type Target = Vec<String>;
+
+	fn deref(&self) -> &Self::Target {
+		&self.items
+	}
+}
+
+impl DerefMut for VaqTagsBuf {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.items
+	}
+}
+
+impl From<Vec<String>> for VaqTagsBuf {
+	fn from(items: Vec<String>) -> Self {
+		VaqTagsBuf { items }
+	}
+}
+
+impl IntoIterator for VaqTagsBuf {
+	type Item = String;
+	type IntoIter = std::vec::IntoIter<String>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.items.into_iter()
+	}
+}
+
+impl<'a> IntoIterator for &'a VaqTagsBuf {
+	type Item = &'a String;
+	type IntoIter = std::slice::Iter<'a, String>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.items.iter()
+	}
+}
+
 #[derive(Clone, Debug, Display)]
 pub struct VaqUrl<'a>(pub &'a BStr);
 
@@ -1,4 +1,11 @@
-use vaquera::git::Git;
+use std::cell::RefCell;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use vaquera::commit_log::{LogQuery, VaqCommit};
+use vaquera::dashboard::{Dashboard, Effect, Key};
+use vaquera::git::{Git, GitError, VaqRepoStatus};
 use vaquera::vaquera::{Vaquera, VaqError};
 use vaquera::storage::Storage;
 use vaquera::tag_filter::TagFilter;
@@ -422,6 +429,123 @@ url = \"git://example.org/upstream_url\"\
 	);
 }
 
+#[test]
+fn dashboard_filters_by_tag_and_pulls_selected_repo() {
+	let pulled = Rc::new(RefCell::new(Vec::<PathBuf>::new()));
+	let recorder = pulled.clone();
+	let git = FakeGit::new().with_pull_callback(Box::new(move |path| recorder.borrow_mut().push(path.to_path_buf())));
+	let mut dashboard = Dashboard::new(&git, dashboard_repos());
+
+	assert_eq!(2, dashboard.visible().len());
+
+	for key in "/frontend".chars().map(Key::Char).chain([Key::Enter]) {
+		assert_eq!(Effect::None, dashboard.handle_key(key));
+	}
+
+	let screen = dashboard.render(80, 10).join("\n");
+	assert!(screen.contains("> web"));
+	assert!(!screen.contains("api"));
+	assert!(screen.contains("filter: frontend"));
+
+	dashboard.handle_key(Key::Char('p'));
+
+	assert_eq!(vec![PathBuf::from("web")], *pulled.borrow());
+	assert_eq!("Pulled web", dashboard.message());
+}
+
+#[test]
+fn dashboard_hands_shell_and_commands_to_terminal() {
+	let git = FakeGit::new();
+	let mut dashboard = Dashboard::new(&git, dashboard_repos());
+
+	assert_eq!(Effect::Shell(PathBuf::from("api")), dashboard.handle_key(Key::Char('s')));
+
+	dashboard.handle_key(Key::Down);
+	for key in ":ls".chars().map(Key::Char) {
+		assert_eq!(Effect::None, dashboard.handle_key(key));
+	}
+	assert!(dashboard.render(80, 10).contains(&"run in web: ls_".to_string()));
+	assert_eq!(
+		Effect::Run { path: PathBuf::from("web"), command: "ls".to_string() },
+		dashboard.handle_key(Key::Enter),
+	);
+
+	dashboard.handle_key(Key::Enter);
+	let details = dashboard.render(80, 12);
+	assert!(details.contains(&"Tags:    frontend".to_string()));
+	assert!(details.contains(&"  origin: git://example.org/web".to_string()));
+
+	dashboard.handle_key(Key::Esc);
+	assert_eq!(Effect::Quit, dashboard.handle_key(Key::Char('q')));
+}
+
+#[test]
+fn set_path_keeps_custom_names() {
+	let mut repos = vaquera::repos::VaqRepos::new_with_repos(dashboard_repos());
+	repos.find_by_path(Path::new("api")).expect("api listed").name = "gateway".to_string();
+
+	assert!(repos.set_path(Path::new("api"), Path::new("services/api")));
+	assert!(repos.set_path(Path::new("web"), Path::new("apps/frontend")));
+
+	let names: Vec<_> = repos.as_vec().iter().map(|repo| (repo.path.clone(), repo.name.clone())).collect();
+	assert_eq!(
+		vec![
+			(PathBuf::from("apps/frontend"), "frontend".to_string()),
+			(PathBuf::from("services/api"), "gateway".to_string()),
+		],
+		names
+	);
+}
+
+#[test]
+fn tag_by_path_picks_one_of_two_repos_with_the_same_name() {
+	let starting_state = "[[repos]]
+path = \"libs/api\"
+tags = []
+
+[[repos]]
+path = \"services/api\"
+tags = []
+";
+
+	let storage = FakeStorage::new()
+		.with_contents(starting_state.to_string())
+		.boxed();
+	let vaquera = Vaquera::new(storage, FakeGit::new().boxed());
+
+	let mut repos = vaquera::repos::VaqRepos::new_with_repos(vaquera.list(&TagFilter::all()).expect("Failed to list repos"));
+	repos.add_tag("backend", vec!["services/api".to_string()]).expect("add_tag failed");
+
+	let tagged: Vec<_> = repos.as_vec().iter().filter(|repo| !repo.tags.is_empty()).map(|repo| repo.path.clone()).collect();
+	assert_eq!(vec![PathBuf::from("services/api")], tagged);
+}
+
+fn dashboard_repos() -> Vec<vaquera::repos::VaqRepo> {
+	let starting_state = "[[repos]]
+path = \"api\"
+tags = [\"backend\"]
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git://example.org/api\"
+
+[[repos]]
+path = \"web\"
+tags = [\"frontend\"]
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git://example.org/web\"
+";
+
+	let storage = FakeStorage::new()
+		.with_contents(starting_state.to_string())
+		.boxed();
+	let vaquera = Vaquera::new(storage, FakeGit::new().boxed());
+
+	vaquera.list(&TagFilter::all()).expect("Failed to list repos")
+}
+
 struct FakeStorage {
 	exists: bool,
 	contents: String,
@@ -462,17 +586,21 @@ impl Storage for FakeStorage {
 		self.exists
 	}
 
-	fn save(&self, state_toml: String) {
+	fn save(&self, state_toml: String) -> io::Result<()> {
 		(self.file_saved_callback)(state_toml);
+		Ok(())
 	}
 
 	fn read(&self) -> String {
 		self.contents.to_owned()
 	}
+
+	fn remove(&self) {}
 }
 
 struct FakeGit {
 	clone_callback: Box<dyn Fn(String, String)>,
+	pull_callback: Box<dyn Fn(&Path)>,
 }
 
 // fluent interface for building up fake git
@@ -480,6 +608,7 @@ impl FakeGit {
 	fn new() -> Self {
 		Self {
 			clone_callback: Box::new(|_, _| {}),
+			pull_callback: Box::new(|_| {}),
 		}
 	}
 
@@ -488,6 +617,11 @@ impl FakeGit {
 		self
 	}
 
+	fn with_pull_callback(mut self, callback: Box<dyn Fn(&Path)>) -> Self {
+		self.pull_callback = callback;
+		self
+	}
+
 	fn boxed(self) -> Box<Self> {
 		Box::new(self)
 	}
@@ -518,4 +652,89 @@ impl Git for FakeGit {
 		(self.clone_callback)(path.to_owned(), url.to_owned());
 		Ok(())
 	}
+
+	fn current_branch(&self, _path: &Path) -> Result<String, GitError> {
+		Ok("main".to_string())
+	}
+
+	fn branch_exists(&self, _path: &Path, _branch_name: &str) -> Result<bool, GitError> {
+		Ok(false)
+	}
+
+	fn create_branch(&self, _path: &Path, _branch_name: &str) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn checkout_branch(&self, _path: &Path, _branch_name: &str) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn delete_branch(&self, _path: &Path, _branch_name: &str) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn apply_patch(&self, _path: &Path, _patch: &[u8]) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn discard_changes(&self, _path: &Path) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn commit_all(&self, _path: &Path, _message: &str) -> Result<bool, GitError> {
+		Ok(false)
+	}
+
+	fn push(&self, _path: &Path, _remote_name: &str, _refspec: &str) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn fetch(&self, _path: &Path) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn pull(&self, path: &Path) -> Result<(), GitError> {
+		(self.pull_callback)(path);
+		Ok(())
+	}
+
+	fn log(&self, _path: &Path, _query: &LogQuery) -> Result<Vec<VaqCommit>, GitError> {
+		Ok(Vec::new())
+	}
+
+	fn status(&self, _path: &Path) -> Result<VaqRepoStatus, GitError> {
+		Ok(VaqRepoStatus::default())
+	}
+
+	fn create_tag(&self, _path: &Path, _tag_name: &str, _message: &str) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn delete_tag(&self, _path: &Path, _tag_name: &str) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn remove_remote(&self, _path: &Path, _remote_name: &str) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn rename_remote(&self, _path: &Path, _remote_name: &str, _new_name: &str) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn set_branch_upstream(&self, _path: &Path, _branch_name: &str, _upstream: &str) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn set_remote_url(&self, _path: &Path, _remote_name: &str, _url: &str) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn set_remote_push_url(&self, _path: &Path, _remote_name: &str, _url: Option<&str>) -> Result<(), GitError> {
+		Ok(())
+	}
+
+	fn read_push_urls(&self, _path: &Path) -> Result<std::collections::BTreeMap<String, String>, GitError> {
+		Ok(std::collections::BTreeMap::new())
+	}
 }
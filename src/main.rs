@@ -1,13 +1,38 @@
-use clap::{Parser, Subcommand};
-use vaquera::exec::exec;
-use vaquera::git::GitImpl;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::{ArgValueCandidates, ArgValueCompleter, CompletionCandidate};
+use clap_complete::env::{CompleteEnv, Shells};
+use vaquera::batch::{BatchChange, VaqBatch};
+use vaquera::changelog::Changelog;
+use vaquera::commands::CustomCommand;
+use vaquera::commit_log::{parse_since, LogQuery};
+use vaquera::doctor::VaqIssue;
+use vaquera::dashboard::{Dashboard, Effect, Key};
+use vaquera::exec::{exec, expand_for_repo, parse_duration, repo_exec, ExecOptions, Interpreter};
+use vaquera::git::{Git, GitImpl};
+use vaquera::grep::{grep, GrepMatch, GrepMode, GrepOptions};
+use vaquera::lookup;
 use vaquera::vaquera::{Vaquera, VaqError, SomeError};
+use vaquera::release::{self, ReleaseError, ReleaseOptions};
+use vaquera::remote_check::{self, RemoteCheck};
+use vaquera::remotes::RemoteEdit;
+use vaquera::report::ReportFormat;
 use vaquera::repos::VaqRepo;
+use vaquera::rewrite::UrlRewrite;
 use vaquera::storage::StorageImpl;
+use vaquera::sync::{RemoteChange, SyncSide, SyncWriteOptions};
 use vaquera::tag_filter::TagFilter;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use inquire::{MultiSelect, Select};
 use log::LevelFilter;
-use std::io::Write;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 /// A CLI tool for managing multiple git repositories
 /// License: A-GPL v3.0
@@ -28,37 +53,83 @@ enum Commands {
 	},
 	/// Remove one or more git repos from vaquera's list. Leaves actual repo on filesystem alone.
 	Remove {
-		#[clap(required = true)]
+		#[clap(required = true, add = ArgValueCandidates::new(complete_repos))]
 		repo_folders: Vec<String>,
 	},
 	/// Show list of repos vaquera knows about. Use "long" to see tags and urls (tab separated format).
 	List {
 		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
 		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
-		#[arg(short, long)]
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
 		tag: Vec<String>,
 		#[clap(short, long)]
 		long: bool,
 	},
-	/// Run any shell command. E.g. `vaquera exec -- git pull`. Double-dash separator indicates end of vaquera's arguments and prevents arguments to your commands being interpreted by vaquera.
+	/// Run any shell command. E.g. `vaquera exec -- git pull`. Double-dash separator indicates end of vaquera's arguments and prevents arguments to your commands being interpreted by vaquera. Placeholders {name}, {path}, {tags}, {branch}, {remote.<name>} and {meta.<key>} in the arguments are replaced per repo, and the command gets VAQUERA_REPO_NAME, VAQUERA_REPO_PATH, VAQUERA_REPO_TAGS, VAQUERA_REPO_REMOTES and VAQUERA_REPO_REMOTE_<NAME> variables.
 	Exec {
 		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
 		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
-		#[arg(short, long)]
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
 		tag: Vec<String>,
 		#[arg(long)]
 		oneline: bool,
+		/// Run the program given as first argument directly, without a shell, passing the other arguments exactly as given
+		#[arg(long, conflicts_with = "shell", requires = "exec_args")]
+		no_shell: bool,
+		/// Shell running the command instead of sh (cmd on Windows)
+		#[arg(long, value_enum)]
+		shell: Option<ExecShell>,
+		/// Kill the command in a repo, and the processes it started, after this long (e.g. 30s, 5m)
+		#[arg(long, value_parser = parse_duration)]
+		timeout: Option<Duration>,
+		/// Stop starting commands in further repos after the first failure
+		#[arg(long)]
+		fail_fast: bool,
+		/// Run a failed command again up to this many times
+		#[arg(long, default_value_t = 0)]
+		retry: usize,
+		/// How long to wait before each retry (e.g. 5s)
+		#[arg(long, value_parser = parse_duration, default_value = "1s")]
+		retry_delay: Duration,
+		/// Also write the stdout and stderr of each repo to files in this folder, e.g. services%2Fapi.stdout.log for services/api
+		#[arg(long)]
+		log_dir: Option<PathBuf>,
+		/// Print a table with the exit code, duration and first error line of each repo at the end
+		#[arg(long)]
+		summary: bool,
+		/// Write a report with the result of each repo, e.g. for CI
+		#[arg(long, value_enum)]
+		report: Option<ExecReport>,
+		/// File to write the report to, vaquera-exec.xml or vaquera-exec.json by default
+		#[arg(long, requires = "report")]
+		report_file: Option<PathBuf>,
 		exec_args: Vec<String>,
 	},
+	/// Run a command registered as `[commands.<name>]` in .vaquera.toml, in the repos its `tags` select. Registered commands can also be run as `vaquera <name>` if no built-in command has that name. Without a name, lists the registered commands
+	Run {
+		name: Option<String>,
+		/// Filter by tags instead of the command's own `tags`. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+		#[arg(long)]
+		oneline: bool,
+	},
 	/// Add/remove repo tags. Use tags to organise repos and allow running commands against subsets of the repo list. Supports comma-separated tag lists (e.g., "tag1,tag2,tag3").
 	Tag {
 		/// Remove this tag from these repo_folders.
 		#[clap(short, long)]
 		remove: bool,
-		#[clap(required = true)]
+		#[clap(required = true, add = ArgValueCompleter::new(complete_tags))]
 		tag: String,
-		#[clap(required = true)]
+		#[clap(required_unless_present = "interactive", add = ArgValueCandidates::new(complete_repos))]
 		repo_folders: Vec<String>,
+		/// Choose the repos in a fuzzy finder instead. Without a terminal, reads repo names or paths from stdin, one per line
+		#[clap(short, long, conflicts_with = "repo_folders")]
+		interactive: bool,
+		/// With --interactive, only offer repos with these tags. Comma-separated tags use AND logic, multiple --tag flags OR logic
+		#[arg(long = "tag", requires = "interactive", add = ArgValueCompleter::new(complete_tags))]
+		pick_tags: Vec<String>,
 	},
 	/// List known tags. Use "long" to list repos per tag.
 	Tags {
@@ -67,56 +138,375 @@ enum Commands {
 	},
 	/// Clone repository from URL and add to vaquera, or clone all configured repos from .vaquera.toml.
 	/// This command behaves in two very different ways depending on whether a remote url was provided:
-	/// If URL is provided: clones from that URL, extracts repo name, adds to vaquera (optionally with tags).
+	/// If URL is provided: clones from that URL into the folder given by the `layout` setting (or named after the repo), adds to vaquera (optionally with tags).
 	/// If URL is omitted: clones all repos from .vaquera.toml (filtered by --tag if specified) skipping existing folders. (Useful for setting up new machines/developers from an existing team configuration)
 	Clone {
 		/// Optional git URL to clone from (e.g., git@github.com:user/repo.git or https://github.com/user/repo).
 		/// If omitted, clones repos defined in .vaquera.toml configuration
 		url: Option<String>,
-		/// Optional addition to URL - target directory name to clone this url into (like git clone). If omitted, extracts name from URL
+		/// Optional addition to URL - target directory name to clone this url into (like git clone). If omitted, uses the `layout` setting or extracts name from URL
 		target_dir: Option<String>,
 		/// When cloning from URL, all specified tags are applied to the new repo.
 		/// When cloning without URL from existing config,
 		/// filters repos to clone from configuration by tags; comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar),
 		/// multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
-		#[arg(short, long)]
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
 		tag: Vec<String>,
 	},
 	/// Sync remotes between git repositories and .vaquera.toml configuration
 	Sync {
 		/// Update .vaquera.toml from remotes in git repositories
-		#[arg(long, conflicts_with = "write_remotes")]
+		#[arg(long, conflicts_with_all = ["write_remotes", "diff"])]
 		read_remotes: bool,
 		/// Update git repositories with remotes from .vaquera.toml
-		#[arg(long, conflicts_with = "read_remotes")]
+		#[arg(long, conflicts_with_all = ["read_remotes", "diff"])]
 		write_remotes: bool,
+		/// Show per repo which remotes writing the config to git would add (+), change (~) or remove (-)
+		#[arg(long, conflicts_with_all = ["read_remotes", "write_remotes"])]
+		diff: bool,
+		/// With --write-remotes, remove remotes from git that are not in .vaquera.toml
+		#[arg(long, requires = "write_remotes")]
+		prune: bool,
+		/// With --write-remotes, overwrite remote URLs and push URLs in git that differ from .vaquera.toml
+		#[arg(long, requires = "write_remotes")]
+		update_urls: bool,
+		/// With --write-remotes, ask which side wins whenever a remote URL differs between .vaquera.toml and git
+		#[arg(long, requires = "write_remotes")]
+		ask: bool,
 		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
 		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
-		#[arg(short, long)]
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
 		tag: Vec<String>,
 	},
+	/// Set up repos for a fork workflow
+	Fork {
+		#[clap(subcommand)]
+		action: ForkAction,
+	},
+	/// Manage remotes across repos, both in .vaquera.toml and in git
+	#[clap(alias = "remote")]
+	Remotes {
+		#[clap(subcommand)]
+		action: RemotesAction,
+	},
 	/// Show detailed information about a repository including tags and remotes
 	/// `repo_id` might be either the repository name or path
 	Show {
-		#[clap(required = true)]
-		repo_id: String,
+		#[clap(required_unless_present = "interactive", add = ArgValueCandidates::new(complete_repos))]
+		repo_id: Option<String>,
+		/// Choose the repo in a fuzzy finder instead. Without a terminal, reads a repo name or path from stdin
+		#[clap(short, long, conflicts_with = "repo_id")]
+		interactive: bool,
+		/// With --interactive, only offer repos with these tags. Comma-separated tags use AND logic, multiple --tag flags OR logic
+		#[arg(short, long, requires = "interactive", add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+	},
+	/// Print the absolute path of a repo, found by path, name or a fuzzy match of its name (e.g. `srvapi` for `service-api`). Works from any folder inside the workspace; without a repo prints the workspace folder
+	Path {
+		#[arg(add = ArgValueCandidates::new(complete_repos))]
+		repo: Option<String>,
+		/// Print the names of all repos instead, for shell completion
+		#[arg(long, conflicts_with = "repo")]
+		list: bool,
+	},
+	/// Print a completion script that completes commands, repo names and paths, tags and remote names from .vaquera.toml, e.g. `source <(vaquera completions bash)` in ~/.bashrc
+	Completions {
+		#[arg(value_parser = ["bash", "elvish", "fish", "powershell", "zsh"])]
+		shell: String,
+	},
+	/// Print a shell function `vq` for `vq cd <repo>` with repo name completion, e.g. `eval "$(vaquera shell-init bash)"` in ~/.bashrc
+	ShellInit {
+		#[arg(value_enum)]
+		shell: Shell,
 	},
 	/// Move a repository to a new location, updating vaquera configuration
 	Move {
 		#[clap(subcommand)]
 		entity: MoveEntity,
 	},
+	/// Search tracked files of all repos in parallel, e.g. `vaquera grep TODO`. Repos without matches are not an error.
+	Grep {
+		pattern: String,
+		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+		/// Only show the names of files that contain matches
+		#[arg(short = 'l', long, conflicts_with = "count")]
+		files_with_matches: bool,
+		/// Show the number of matching lines per file
+		#[arg(short, long)]
+		count: bool,
+		#[arg(short, long)]
+		ignore_case: bool,
+		/// Treat the pattern as a literal string instead of a regular expression
+		#[arg(short = 'F', long)]
+		fixed_strings: bool,
+		/// Print matches as a JSON array
+		#[arg(long)]
+		json: bool,
+	},
+	/// Show commits of all repos merged into one stream, newest first (tab separated: date, repo, commit, author, summary)
+	Log {
+		/// Only show commits more recent than a date, e.g. "2024-05-01" or "2 weeks ago"
+		#[arg(long)]
+		since: Option<String>,
+		/// Only show commits whose author name or email contains this text
+		#[arg(long)]
+		author: Option<String>,
+		/// Limit the number of commits shown
+		#[arg(short = 'n', long)]
+		max_count: Option<usize>,
+		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+	},
+	/// Generate release notes from the commits of all repos between two revisions, grouped by Conventional Commit type and repo
+	Changelog {
+		/// Git revision (tag, branch or commit) the previous release was made from, resolved in each repo; its history is excluded
+		#[arg(long)]
+		from: String,
+		/// Git revision of the new release, resolved in each repo
+		#[arg(long, default_value = "HEAD")]
+		to: String,
+		#[arg(long, value_enum, default_value_t = ChangelogFormat::Markdown)]
+		format: ChangelogFormat,
+		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+	},
+	/// Report where .vaquera.toml and the filesystem disagree: missing folders, non-git folders, differing remotes, duplicate names, paths outside the workspace and git repos not in the config
+	Doctor {
+		/// Offer to fix each problem found (remove missing repos, add untracked repos, read remotes from git)
+		#[arg(long)]
+		fix: bool,
+		/// Apply every fix without asking
+		#[arg(short, long, requires = "fix")]
+		yes: bool,
+	},
+	/// Move repos to where the `layout` template in .vaquera.toml puts them (e.g. layout = "{host}/{owner}/{name}"), based on the URL of their origin remote
+	Relayout {
+		/// Only list the moves that would be made
+		#[arg(long)]
+		dry_run: bool,
+	},
+	/// Full-screen dashboard of all repos with branch, dirty state, ahead/behind and tags. Keys: `/` filters by tag (like --tag, spaces between OR groups), `f` fetches, `p` pulls, `s` opens a shell in the repo, `:` runs a command (or one registered in .vaquera.toml, by name) there, enter shows details, `q` quits
+	Ui,
+	/// List recent changes to .vaquera.toml, most recent first, numbered for `undo`
+	History,
+	/// Revert the most recent changes to .vaquera.toml (and move back repos they moved)
+	Undo {
+		/// How many changes to revert
+		#[arg(default_value_t = 1)]
+		steps: usize,
+	},
+	/// Remove repos whose folder no longer exists from .vaquera.toml
+	Prune {
+		/// Only list the repos that would be removed
+		#[arg(long)]
+		dry_run: bool,
+		/// Also remove repos whose folder exists but is not a git repository
+		#[arg(long)]
+		not_git: bool,
+	},
+	/// Coordinate a release across repos
+	Release {
+		#[clap(subcommand)]
+		action: ReleaseAction,
+	},
+	/// Roll out a patch or scripted edit to many repos on a dedicated branch, and track it until pushed or abandoned
+	Batch {
+		#[clap(subcommand)]
+		action: BatchAction,
+	},
+	/// A command registered in .vaquera.toml, run by name
+	#[command(external_subcommand)]
+	Custom(Vec<String>),
 }
 
 #[derive(Subcommand)]
 enum MoveEntity {
-	/// Move a repository to a new location
+	/// Move repositories to a new location, like `mv`: a single repository is renamed to the destination, several are moved into it.
+	/// Works across filesystems, keeps worktrees working and leaves everything in place if the config can't be updated.
 	Repo {
-		/// Current path of the repository
-		old_path: String,
-		/// New path for the repository
-		new_path: String,
+		/// Repositories to move, by path or name, followed by the destination. With --interactive only the destination,
+		/// a folder the chosen repos are moved into
+		#[clap(required = true, num_args = 1.., add = ArgValueCandidates::new(complete_repos))]
+		paths: Vec<String>,
+		/// Choose the repos in a fuzzy finder instead. Without a terminal, reads repo names or paths from stdin, one per line
+		#[clap(short, long)]
+		interactive: bool,
+		/// With --interactive, only offer repos with these tags. Comma-separated tags use AND logic, multiple --tag flags OR logic
+		#[arg(short, long, requires = "interactive", add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+	},
+}
+
+#[derive(Subcommand)]
+enum ForkAction {
+	/// Rename the canonical `origin` remote to `upstream`, add the fork of <owner> as `origin` (same URL with the owner swapped) and make the current branch track `upstream`
+	Setup {
+		/// User or organisation owning the forks
+		#[arg(long)]
+		owner: String,
+		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+	},
+}
+
+#[derive(Subcommand)]
+enum RemotesAction {
+	/// Add a remote to each repo. The URL may contain {name} (repo name) and {path} (repo path), e.g. `git@github.com:myfork/{name}.git`
+	Add {
+		name: String,
+		url: String,
+		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+	},
+	/// Rename a remote in each repo
+	Rename {
+		#[arg(add = ArgValueCandidates::new(complete_remotes))]
+		name: String,
+		new_name: String,
+		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+	},
+	/// Remove a remote from each repo
+	Remove {
+		#[arg(add = ArgValueCandidates::new(complete_remotes))]
+		name: String,
+		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+	},
+	/// Connect to every configured remote and report whether it is reachable, its default branch and whether the local default branch tracks it. Moved (redirected) and missing remotes are flagged
+	Check {
+		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+	},
+	/// Change the URL of a remote in each repo. The URL may contain {name} and {path} like for `add`
+	SetUrl {
+		#[arg(add = ArgValueCandidates::new(complete_remotes))]
+		name: String,
+		url: String,
+		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+	},
+	/// Replace the start of remote URLs in .vaquera.toml and in git, e.g. after a host move. The prefix matches SSH and HTTPS spellings alike, so it can also switch protocols (e.g. `--from git@git.old.corp: --to https://git.new.corp/`)
+	Rewrite {
+		/// URL prefix to replace
+		#[arg(long)]
+		from: String,
+		/// Replacement prefix
+		#[arg(long)]
+		to: String,
+		/// Only show the URLs that would be rewritten
+		#[arg(long)]
+		dry_run: bool,
+		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+	},
+}
+
+#[derive(Subcommand)]
+enum ReleaseAction {
+	/// Create an annotated tag on HEAD of each repo. Every repo must be clean and in sync with its upstream; if tagging fails in one repo, tags already created are removed.
+	Tag {
+		version: String,
+		/// Tag message. Defaults to "Release <version>"
+		#[arg(short, long)]
+		message: Option<String>,
+		/// Only check that repos are ready and show what would be tagged
+		#[arg(long)]
+		dry_run: bool,
+		/// Push the tag once every repo has been tagged
+		#[arg(long)]
+		push: bool,
+		/// Remote to push the tag to
+		#[arg(long, default_value = "origin", requires = "push", add = ArgValueCandidates::new(complete_remotes))]
+		remote: String,
+		/// Compare with the upstream branches as last fetched instead of fetching them first
+		#[arg(long)]
+		no_fetch: bool,
+		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+	},
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Shell {
+	Bash,
+	Zsh,
+	Fish,
+	Nu,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExecShell {
+	Bash,
+	Zsh,
+	Pwsh,
+	Nu,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExecReport {
+	Junit,
+	Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ChangelogFormat {
+	Markdown,
+	Json,
+}
+
+#[derive(Subcommand)]
+enum BatchAction {
+	/// Create a branch in each repo, apply the change and commit it. The change is either a patch file, or a command given after `--` (e.g. `vaquera batch apply -b bump -m "Bump deps" -- cargo update`)
+	Apply {
+		/// Name of the branch created in each repo
+		#[arg(short, long)]
+		branch: String,
+		/// Commit message used in each repo
+		#[arg(short, long)]
+		message: String,
+		/// Patch file to apply, as produced by `git diff`
+		#[arg(long, conflicts_with = "script", required_unless_present = "script")]
+		patch: Option<PathBuf>,
+		/// Filter by tags. Comma-separated tags use AND logic (e.g., "foo,bar" = foo AND bar).
+		/// Multiple --tag flags use OR logic (e.g., "--tag foo,bar --tag baz" = (foo AND bar) OR baz).
+		#[arg(short, long, add = ArgValueCompleter::new(complete_tags))]
+		tag: Vec<String>,
+		/// Command to run in each repo instead of applying a patch
+		script: Vec<String>,
+	},
+	/// Show which repos changed, failed or were no-ops in the current batch
+	Status,
+	/// Push the batch branch of every changed repo
+	Push {
+		#[arg(long, default_value = "origin", add = ArgValueCandidates::new(complete_remotes))]
+		remote: String,
 	},
+	/// Switch repos back to their original branch, delete the batch branch and forget the batch
+	Abandon,
 }
 
 fn main() {
@@ -125,6 +515,9 @@ fn main() {
 		.filter(None, LevelFilter::Info) // turn on log output
 		.init();
 
+	// Answers completion requests from the scripts printed by `completions`
+	CompleteEnv::with_factory(Args::command).complete();
+
 	match &Args::parse_from(wild::args()).command {
 		Some(Commands::Add { repo_folders }) =>
 			add(repo_folders.to_owned()),
@@ -155,23 +548,70 @@ fn main() {
 		Some(Commands::Exec {
 			tag: tag_args,
 			oneline,
+			no_shell,
+			shell,
+			timeout,
+			fail_fast,
+			retry,
+			retry_delay,
+			log_dir,
+			summary,
+			report,
+			report_file,
 			exec_args,
 		}) => {
+			let interpreter = match (*no_shell, *shell) {
+				(true, _) => Interpreter::Direct,
+				(false, Some(ExecShell::Bash)) => Interpreter::Bash,
+				(false, Some(ExecShell::Zsh)) => Interpreter::Zsh,
+				(false, Some(ExecShell::Pwsh)) => Interpreter::Pwsh,
+				(false, Some(ExecShell::Nu)) => Interpreter::Nu,
+				(false, None) => Interpreter::System,
+			};
 			let filter = TagFilter::from_cli_args(tag_args);
+			let vaquera = init_vaquera();
+			let hooks = vaquera.settings().expect("Failed to read settings").hooks;
 			exec(
+				&GitImpl {},
 				exec_args.to_owned(),
-				init_vaquera()
+				vaquera
 					.list(&filter)
 					.expect("Failed to list repositories for exec"),
-				*oneline,
+				&ExecOptions {
+					oneline: *oneline,
+					hooks,
+					interpreter,
+					timeout: *timeout,
+					fail_fast: *fail_fast,
+					retries: *retry,
+					retry_delay: *retry_delay,
+					log_dir: log_dir.clone(),
+					summary: *summary,
+					report: report.map(|report| match report {
+						ExecReport::Junit => ReportFormat::Junit,
+						ExecReport::Json => ReportFormat::Json,
+					}),
+					report_file: report_file.clone(),
+					..Default::default()
+				},
 			);
 		}
 		Some(Commands::Tag {
 			tag: tag_name,
 			repo_folders,
 			remove,
+			interactive,
+			pick_tags,
 		}) => {
 			let tags: Vec<&str> = tag_name.split(',').map(|s| s.trim()).collect();
+			let repo_folders = &if *interactive {
+				pick_repos("Tag which repos?", true, pick_tags)
+					.into_iter()
+					.map(|repo| repo.path.to_string_lossy().to_string())
+					.collect()
+			} else {
+				repo_folders.to_owned()
+			};
 
 			for tag in tags {
 				let result = if *remove {
@@ -191,6 +631,10 @@ fn main() {
 		Some(Commands::Sync {
 			read_remotes,
 			write_remotes,
+			diff,
+			prune,
+			update_urls,
+			ask,
 			tag: tag_args,
 		}) => {
 			let filter = TagFilter::from_cli_args(tag_args);
@@ -200,24 +644,109 @@ fn main() {
 					.sync_read_remotes(&filter)
 					.expect("Sync read failed");
 			} else if *write_remotes {
+				let options = SyncWriteOptions {
+					prune: *prune,
+					update_urls: *update_urls,
+				};
+				let resolve: Option<&dyn Fn(&Path, &RemoteChange) -> SyncSide> = if *ask {
+					Some(&ask_sync_side)
+				} else {
+					None
+				};
 				init_vaquera()
-					.sync_write_remotes(&filter)
+					.sync_write_remotes(&filter, &options, resolve)
 					.expect("Sync write failed");
+			} else if *diff {
+				sync_diff(&filter);
 			} else {
-				eprintln!("Error: Must specify either --read-remotes or --write-remotes");
+				eprintln!("Error: Must specify either --read-remotes, --write-remotes or --diff");
 				std::process::exit(1);
 			}
 		}
 
-		Some(Commands::Show { repo_id }) => {
-			show(repo_id);
-		}
+		Some(Commands::Fork { action }) => match action {
+			ForkAction::Setup { owner, tag: tag_args } => {
+				let filter = TagFilter::from_cli_args(tag_args);
+
+				if let Err(error) = init_vaquera().fork_setup(&filter, owner) {
+					eprintln!("Error: {}", error.message());
+					std::process::exit(1);
+				}
+			}
+		},
+
+		Some(Commands::Remotes { action }) => match action {
+			RemotesAction::Add { name, url, tag: tag_args } => edit_remotes(
+				&RemoteEdit::Add { name: name.clone(), url: url.clone() },
+				tag_args,
+			),
+
+			RemotesAction::Rename { name, new_name, tag: tag_args } => edit_remotes(
+				&RemoteEdit::Rename { name: name.clone(), new_name: new_name.clone() },
+				tag_args,
+			),
+
+			RemotesAction::Remove { name, tag: tag_args } => edit_remotes(
+				&RemoteEdit::Remove { name: name.clone() },
+				tag_args,
+			),
+
+			RemotesAction::SetUrl { name, url, tag: tag_args } => edit_remotes(
+				&RemoteEdit::SetUrl { name: name.clone(), url: url.clone() },
+				tag_args,
+			),
+
+			RemotesAction::Check { tag: tag_args } => check_remotes(tag_args),
+
+			RemotesAction::Rewrite { from, to, dry_run, tag: tag_args } => {
+				rewrite_remotes(&UrlRewrite::new(from, to), *dry_run, tag_args);
+			}
+		},
+
+		Some(Commands::Show { repo_id, interactive, tag: tag_args }) => match repo_id {
+			Some(repo_id) if !*interactive => show(repo_id),
+			_ => {
+				let picked = pick_repos("Show which repo?", false, tag_args);
+				show(&picked[0].path.to_string_lossy());
+			}
+		},
+
+		Some(Commands::Path { repo, list }) => path(repo, *list),
+
+		Some(Commands::Completions { shell }) => completions(shell),
+
+		Some(Commands::ShellInit { shell }) => print!("{}", shell_init(*shell)),
 
 		Some(Commands::Move { entity }) => match entity {
-			MoveEntity::Repo { old_path, new_path } => {
-				match init_vaquera().move_repo(old_path, new_path) {
-					Ok(_) => {
-						eprintln!("Moved {} to {}", old_path, new_path);
+			MoveEntity::Repo { paths, interactive, tag: tag_args } => {
+				let (destination, repo_ids) = paths.split_last().expect("clap requires a destination");
+				let destination = Path::new(destination);
+
+				let result = if *interactive {
+					if !repo_ids.is_empty() {
+						eprintln!("Error: With --interactive only give the destination");
+						std::process::exit(1);
+					}
+					let repo_ids: Vec<String> = pick_repos(&format!("Move which repos into {}?", destination.display()), true, tag_args)
+						.into_iter()
+						.map(|repo| repo.path.to_string_lossy().into_owned())
+						.collect();
+
+					// However many repos were picked, the destination is the folder to move them into
+					init_vaquera().move_repos_into(&repo_ids, destination)
+				} else {
+					if repo_ids.is_empty() {
+						eprintln!("Error: Give the repos to move followed by the destination");
+						std::process::exit(1);
+					}
+					init_vaquera().move_repos(repo_ids, destination)
+				};
+
+				match result {
+					Ok(moved) => {
+						for (old_path, new_path) in moved {
+							eprintln!("Moved {} to {}", old_path.display(), new_path.display());
+						}
 					}
 					Err(error) => {
 						eprintln!("Error: {}", error.message());
@@ -227,6 +756,136 @@ fn main() {
 			}
 		},
 
+		Some(Commands::Relayout { dry_run }) => relayout(*dry_run),
+
+		Some(Commands::Ui) => ui(),
+
+		Some(Commands::History) => history(),
+
+		Some(Commands::Undo { steps }) => match init_vaquera().undo(*steps) {
+			Ok(undone) => {
+				for entry in undone {
+					println!("Undid {}", entry.action);
+				}
+			}
+			Err(error) => {
+				eprintln!("Error: {}", error.message());
+				std::process::exit(1);
+			}
+		},
+
+		Some(Commands::Grep {
+			pattern,
+			tag: tag_args,
+			files_with_matches,
+			count,
+			ignore_case,
+			fixed_strings,
+			json,
+		}) => {
+			let mode = if *files_with_matches {
+				GrepMode::FilesWithMatches
+			} else if *count {
+				GrepMode::Count
+			} else {
+				GrepMode::Lines
+			};
+			let options = GrepOptions {
+				mode,
+				ignore_case: *ignore_case,
+				fixed_strings: *fixed_strings,
+			};
+			let filter = TagFilter::from_cli_args(tag_args);
+			grep_repos(
+				pattern,
+				init_vaquera()
+					.list(&filter)
+					.expect("Failed to list repositories for grep"),
+				&options,
+				*json,
+			);
+		}
+
+		Some(Commands::Log {
+			since,
+			author,
+			max_count,
+			tag: tag_args,
+		}) => log(since, author, *max_count, tag_args),
+
+		Some(Commands::Changelog {
+			from,
+			to,
+			format,
+			tag: tag_args,
+		}) => changelog(from, to, *format, tag_args),
+
+		Some(Commands::Doctor { fix, yes }) => doctor(*fix, *yes),
+
+		Some(Commands::Prune { dry_run, not_git }) => {
+			match init_vaquera().prune(*not_git, *dry_run) {
+				Ok(pruned) => {
+					let verb = if *dry_run { "Would remove" } else { "Removed" };
+					for repo in &pruned {
+						println!("{} {}", verb, repo.path.display());
+					}
+				}
+				Err(error) => {
+					eprintln!("Error: {}", error.message());
+					std::process::exit(1);
+				}
+			}
+		}
+
+		Some(Commands::Release { action }) => match action {
+			ReleaseAction::Tag {
+				version,
+				message,
+				dry_run,
+				push,
+				remote,
+				no_fetch,
+				tag: tag_args,
+			} => {
+				let options = ReleaseOptions {
+					message: message.to_owned(),
+					dry_run: *dry_run,
+					push: push.then(|| remote.to_owned()),
+					no_fetch: *no_fetch,
+				};
+				release_tag(version, &options, tag_args);
+			}
+		},
+
+		Some(Commands::Batch { action }) => batch(action),
+
+		Some(Commands::Run { name, tag: tag_args, oneline }) => match name {
+			Some(name) => run_command(name, tag_args, *oneline),
+			None => list_commands(),
+		},
+
+		// `vaquera <name> [options]` is short for `vaquera run <name> [options]`
+		Some(Commands::Custom(args)) => {
+			let registered = init_vaquera()
+				.settings()
+				.map(|settings| args.first().is_some_and(|name| settings.commands.contains_key(name)))
+				.unwrap_or(false);
+
+			// Neither built in nor registered: clap's error, which suggests a built-in for typos
+			if !registered {
+				if let Err(error) = Args::command().allow_external_subcommands(false).try_get_matches_from(wild::args()) {
+					error.exit();
+				}
+			}
+
+			let run_args = ["vaquera", "run"].into_iter().map(String::from).chain(args.iter().cloned());
+
+			match Args::parse_from(run_args).command {
+				Some(Commands::Run { name: Some(name), tag: tag_args, oneline }) => run_command(&name, &tag_args, oneline),
+				_ => unreachable!("external subcommands have a name"),
+			}
+		}
+
 		None => {
 			panic!("no command") // this doesn't happen because help shows instead
 		}
@@ -303,13 +962,818 @@ fn clone_from_url(git_url: &str, target_dir: &Option<String>, tag_args: &[String
 	}
 }
 
+/// Runs from the workspace root, wherever below it vaquera was started, so that `vq cd` works
+/// from anywhere in the workspace.
+fn path(repo: &Option<String>, list: bool) {
+	let root = std::env::current_dir()
+		.ok()
+		.and_then(|cwd| lookup::find_workspace_root(&cwd, STATE_FILE))
+		.unwrap_or_else(|| {
+			eprintln!("Error: No {STATE_FILE} found in this folder or any folder above it");
+			std::process::exit(1);
+		});
+	std::env::set_current_dir(&root).expect("Failed to change to workspace folder");
+
+	let repos = init_vaquera()
+		.list(&TagFilter::all())
+		.expect("Failed to list repositories");
+
+	if list {
+		for repo in &repos {
+			println!("{}", repo.name);
+		}
+		return;
+	}
+
+	let Some(query) = repo else {
+		println!("{}", root.display());
+		return;
+	};
+
+	match lookup::find_repo(&repos, query) {
+		Ok(repo) => println!("{}", root.join(&repo.path).display()),
+		Err(error) => {
+			eprintln!("Error: {error}");
+			std::process::exit(1);
+		}
+	}
+}
+
+fn completions(shell: &str) {
+	let completer = Shells::builtins()
+		.completer(shell)
+		.expect("clap only accepts supported shells");
+
+	completer
+		.write_registration("COMPLETE", "vaquera", "vaquera", "vaquera", &mut std::io::stdout())
+		.expect("Failed to write completion script");
+}
+
+/// Repo paths, and names where they differ from the path. Completion must not print errors, so an
+/// unreadable config completes nothing.
+fn complete_repos() -> Vec<CompletionCandidate> {
+	let repos = completion_vaquera()
+		.and_then(|vaquera| vaquera.list(&TagFilter::all()).ok())
+		.unwrap_or_default();
+	let mut candidates: Vec<CompletionCandidate> = repos
+		.iter()
+		.map(|repo| CompletionCandidate::new(&repo.path))
+		.collect();
+
+	candidates.extend(
+		repos
+			.iter()
+			.filter(|repo| repo.path != Path::new(&repo.name))
+			.map(|repo| CompletionCandidate::new(&repo.name).help(Some(repo.path.display().to_string().into()))),
+	);
+
+	candidates
+}
+
+/// Tag names, completing the last one of a comma-separated list like `backend,ru`.
+fn complete_tags(current: &OsStr) -> Vec<CompletionCandidate> {
+	let current = current.to_string_lossy();
+	let done = current.rfind(',').map_or("", |comma| &current[..=comma]);
+	let partial = &current[done.len()..];
+
+	completion_vaquera()
+		.and_then(|vaquera| vaquera.tags().ok())
+		.unwrap_or_default()
+		.into_iter()
+		.filter(|tag| tag.starts_with(partial) && !done.split(',').any(|typed| typed == tag))
+		.map(|tag| CompletionCandidate::new(format!("{done}{tag}")))
+		.collect()
+}
+
+/// Names of the remotes configured in any repo.
+fn complete_remotes() -> Vec<CompletionCandidate> {
+	let mut names: Vec<String> = completion_vaquera()
+		.and_then(|vaquera| vaquera.list(&TagFilter::all()).ok())
+		.unwrap_or_default()
+		.iter()
+		.flat_map(|repo| repo.remotes.iter().map(|(name, _)| name.to_string()))
+		.collect();
+
+	names.sort();
+	names.dedup();
+	names.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// The workspace around the current folder, like `path` finds it, as the shell asks for
+/// completions from wherever it is. `None` outside a workspace.
+fn completion_vaquera() -> Option<Vaquera> {
+	let root = std::env::current_dir()
+		.ok()
+		.and_then(|cwd| lookup::find_workspace_root(&cwd, STATE_FILE))?;
+	std::env::set_current_dir(&root).ok()?;
+
+	Some(init_vaquera())
+}
+
+fn shell_init(shell: Shell) -> &'static str {
+	match shell {
+		Shell::Bash => include_str!("shell/vq.bash"),
+		Shell::Zsh => include_str!("shell/vq.zsh"),
+		Shell::Fish => include_str!("shell/vq.fish"),
+		Shell::Nu => include_str!("shell/vq.nu"),
+	}
+}
+
+fn ui() {
+	if !std::io::stdout().is_terminal() {
+		eprintln!("Error: ui needs a terminal");
+		std::process::exit(1);
+	}
+
+	let repos = init_vaquera()
+		.list(&TagFilter::all())
+		.expect("Failed to list repositories");
+	let git = GitImpl {};
+	let mut dashboard = Dashboard::new(&git, repos);
+	let commands = init_vaquera().settings().expect("Failed to read settings").commands;
+
+	// Leave the screen before a panic message is printed, or it vanishes with the screen
+	let default_hook = std::panic::take_hook();
+	std::panic::set_hook(Box::new(move |info| {
+		let _ = leave_screen(&mut std::io::stdout());
+		default_hook(info);
+	}));
+
+	let mut stdout = std::io::stdout();
+	let screen = Screen::enter(&mut stdout).expect("Failed to set up terminal");
+	let result = run_dashboard(&git, &mut dashboard, &commands, &mut stdout);
+	drop(screen);
+
+	if let Err(error) = result {
+		eprintln!("Error: {error}");
+		std::process::exit(1);
+	}
+}
+
+/// `commands` are the ones registered in .vaquera.toml, which `:` runs by name.
+fn run_dashboard(
+	git: &dyn Git,
+	dashboard: &mut Dashboard,
+	commands: &BTreeMap<String, CustomCommand>,
+	stdout: &mut std::io::Stdout,
+) -> std::io::Result<()> {
+	loop {
+		let (width, height) = terminal::size()?;
+		queue!(stdout, Clear(ClearType::All))?;
+		for (row, line) in dashboard.render(width.into(), height.into()).iter().enumerate() {
+			queue!(stdout, MoveTo(0, row as u16), Print(line))?;
+		}
+		stdout.flush()?;
+
+		let Event::Key(event) = event::read()? else {
+			continue;
+		};
+		if event.kind != KeyEventKind::Press {
+			continue;
+		}
+
+		let key = match event.code {
+			KeyCode::Up => Key::Up,
+			KeyCode::Down => Key::Down,
+			KeyCode::Enter => Key::Enter,
+			KeyCode::Esc => Key::Esc,
+			KeyCode::Backspace => Key::Backspace,
+			KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+			KeyCode::Char(c) => Key::Char(c),
+			_ => continue,
+		};
+
+		match dashboard.handle_key(key) {
+			Effect::None => {}
+			Effect::Quit => return Ok(()),
+			Effect::Shell(path) => {
+				let shell = shell_program();
+
+				leave_screen(stdout)?;
+				let status = std::process::Command::new(&shell).current_dir(&path).status();
+				enter_screen(stdout)?;
+
+				dashboard.set_message(match status {
+					Ok(_) => format!("Back from shell in {}", path.display()),
+					Err(error) => format!("Failed to start {shell}. {error}"),
+				});
+				dashboard.refresh_selected();
+			}
+			Effect::Run { path, command } => {
+				let (exec_args, options) = match commands.get(command.trim()) {
+					Some(saved) => (saved.exec_args(), saved.exec_options(false)),
+					None => (vec![command.clone()], ExecOptions::default()),
+				};
+				let dir = options.dir.as_ref().map_or(path.clone(), |dir| path.join(dir));
+				let (exec_args, env) = match dashboard.selected() {
+					Some(row) => expand_for_repo(git, &row.repo, &exec_args, &options.env),
+					None => (exec_args, options.env.clone()),
+				};
+
+				leave_screen(stdout)?;
+				let status = repo_exec(&dir, &exec_args, &env, &options);
+				println!("Press enter to return");
+				std::io::stdin().read_line(&mut String::new())?;
+				enter_screen(stdout)?;
+
+				dashboard.set_message(match status {
+					Ok(status) if status.success() => format!("Ran `{command}` in {}", path.display()),
+					Ok(status) => format!("`{command}` failed in {}: {status}", path.display()),
+					Err(error) => format!("Failed to run `{command}`. {error}"),
+				});
+				dashboard.refresh_selected();
+			}
+		}
+	}
+}
+
+/// The dashboard's screen: raw mode in the alternate screen, left again when dropped, so also
+/// when the dashboard fails or panics.
+struct Screen;
+
+impl Screen {
+	fn enter(stdout: &mut std::io::Stdout) -> std::io::Result<Screen> {
+		enter_screen(stdout)?;
+		Ok(Screen)
+	}
+}
+
+impl Drop for Screen {
+	fn drop(&mut self) {
+		if let Err(error) = leave_screen(&mut std::io::stdout()) {
+			eprintln!("Warning: Failed to restore terminal. {error}");
+		}
+	}
+}
+
+fn enter_screen(stdout: &mut std::io::Stdout) -> std::io::Result<()> {
+	execute!(stdout, EnterAlternateScreen, Hide)?;
+	terminal::enable_raw_mode()
+}
+
+fn leave_screen(stdout: &mut std::io::Stdout) -> std::io::Result<()> {
+	terminal::disable_raw_mode()?;
+	execute!(stdout, LeaveAlternateScreen, Show)
+}
+
+/// The user's interactive shell.
+fn shell_program() -> String {
+	#[cfg(windows)]
+	let shell = std::env::var("COMSPEC").unwrap_or_else(|_| "cmd".to_string());
+	#[cfg(not(windows))]
+	let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+
+	shell
+}
+
+fn run_command(name: &str, tag_args: &[String], oneline: bool) {
+	let vaquera = init_vaquera();
+	let settings = vaquera.settings().expect("Failed to read settings");
+
+	let Some(command) = settings.commands.get(name) else {
+		eprintln!("Error: Unknown command '{name}'. Run `vaquera run` to list the commands in .vaquera.toml, or `vaquera help` for built-in ones");
+		std::process::exit(1);
+	};
+
+	let tag_args = if tag_args.is_empty() { &command.tags } else { tag_args };
+	let repos = vaquera
+		.list(&TagFilter::from_cli_args(tag_args))
+		.expect("Failed to list repositories for run");
+
+	let options = ExecOptions { hooks: settings.hooks.clone(), ..command.exec_options(oneline) };
+	exec(&GitImpl {}, command.exec_args(), repos, &options);
+}
+
+fn list_commands() {
+	let settings = init_vaquera().settings().expect("Failed to read settings");
+
+	if settings.commands.is_empty() {
+		println!("No commands registered. Add e.g. [commands.test] with run = \"cargo test\" to .vaquera.toml");
+	}
+
+	for (name, command) in &settings.commands {
+		println!("{name}\t{}", command.description.as_deref().unwrap_or(&command.run));
+	}
+}
+
+fn history() {
+	let entries = init_vaquera().history().expect("Failed to read history");
+
+	if entries.is_empty() {
+		println!("No changes recorded");
+	}
+
+	for (number, entry) in entries.iter().enumerate() {
+		println!("{:>3}  {}  {}", number + 1, entry.date(), entry.action);
+	}
+}
+
+fn relayout(dry_run: bool) {
+	let mut vaquera = init_vaquera();
+	let settings = vaquera.settings().expect("Failed to read settings");
+
+	let Some(layout) = settings.layout else {
+		eprintln!("Error: No layout configured. Set e.g. layout = \"{{host}}/{{owner}}/{{name}}\" in .vaquera.toml");
+		std::process::exit(1);
+	};
+
+	let moved = vaquera
+		.relayout(&layout, dry_run)
+		.unwrap_or_else(|error| {
+			eprintln!("Error: {}", error.message());
+			std::process::exit(1);
+		});
+
+	if moved.is_empty() {
+		println!("All repos already follow the layout");
+	}
+
+	for (old_path, new_path) in moved {
+		if dry_run {
+			println!("Would move {} to {}", old_path.display(), new_path.display());
+		} else {
+			println!("Moved {} to {}", old_path.display(), new_path.display());
+		}
+	}
+}
+
+fn grep_repos(pattern: &str, repos: Vec<VaqRepo>, options: &GrepOptions, json: bool) {
+	let mut matches: Vec<GrepMatch> = Vec::new();
+	let mut error_count = 0;
+
+	for repo_result in grep(pattern, repos, options) {
+		match repo_result.result {
+			Ok(repo_matches) => matches.extend(repo_matches),
+			Err(error) => {
+				eprintln!("Warning: {}: {}", repo_result.repo.path.display(), error);
+				error_count += 1;
+			}
+		}
+	}
+
+	if json {
+		println!(
+			"{}",
+			serde_json::to_string_pretty(&matches).expect("Failed to serialize matches")
+		);
+	} else {
+		for grep_match in &matches {
+			println!("{}", grep_match.to_line());
+		}
+	}
+
+	if error_count > 0 {
+		eprintln!("{error_count} repos failed to search");
+		std::process::exit(1);
+	}
+}
+
+fn log(since: &Option<String>, author: &Option<String>, max_count: Option<usize>, tag_args: &[String]) {
+	let since = match since.as_deref().map(parse_since).transpose() {
+		Ok(since) => since,
+		Err(error) => {
+			eprintln!("Error: {}", error);
+			std::process::exit(1);
+		}
+	};
+
+	let query = LogQuery {
+		since,
+		author: author.to_owned(),
+		max_count,
+		..Default::default()
+	};
+
+	let filter = TagFilter::from_cli_args(tag_args);
+	let vaq_log = init_vaquera()
+		.log(&filter, &query)
+		.expect("Failed to list repositories for log");
+
+	for commit in &vaq_log.commits {
+		println!(
+			"{}\t{}\t{}\t{}\t{}",
+			commit.date(),
+			commit.repo,
+			commit.short_id(),
+			commit.author_name,
+			commit.summary
+		);
+	}
+
+	for (path, error) in &vaq_log.failed {
+		eprintln!("Warning: Could not read log from {}: {}", path.display(), error);
+	}
+
+	if !vaq_log.failed.is_empty() {
+		eprintln!("{} repos failed to read", vaq_log.failed.len());
+		std::process::exit(1);
+	}
+}
+
+/// `from` and `to` are git revisions resolved in each repo on its own. There are no
+/// workspace snapshots of repo HEADs to name instead.
+fn changelog(from: &str, to: &str, format: ChangelogFormat, tag_args: &[String]) {
+	let query = LogQuery {
+		from: Some(from.to_string()),
+		to: Some(to.to_string()),
+		..Default::default()
+	};
+
+	let filter = TagFilter::from_cli_args(tag_args);
+	let vaq_log = init_vaquera()
+		.log(&filter, &query)
+		.expect("Failed to list repositories for changelog");
+
+	let changelog = Changelog::new(&vaq_log.commits);
+
+	match format {
+		ChangelogFormat::Markdown => print!("{}", changelog.to_markdown()),
+		ChangelogFormat::Json => println!(
+			"{}",
+			serde_json::to_string_pretty(&changelog).expect("Failed to serialize changelog")
+		),
+	}
+
+	for (path, error) in &vaq_log.failed {
+		eprintln!("Warning: Could not read commits from {}: {}", path.display(), error);
+	}
+
+	if !vaq_log.failed.is_empty() {
+		eprintln!("{} repos failed to read", vaq_log.failed.len());
+		std::process::exit(1);
+	}
+}
+
+fn doctor(fix: bool, yes: bool) {
+	let workspace_root = std::env::current_dir().expect("failed to get current working directory");
+	let issues = init_vaquera()
+		.doctor(&workspace_root)
+		.expect("Failed to check repositories");
+
+	if issues.is_empty() {
+		println!("No problems found");
+		return;
+	}
+
+	for issue in &issues {
+		println!("{issue}");
+	}
+
+	if !fix {
+		eprintln!("{} problems found", issues.len());
+		std::process::exit(1);
+	}
+
+	println!();
+
+	for issue in issues {
+		let result = match &issue {
+			VaqIssue::MissingFolder { path } | VaqIssue::NotGitRepo { path } => {
+				if !confirm(&format!("Remove {} from config?", path.display()), yes) {
+					continue;
+				}
+				init_vaquera().remove_repos_by_path(std::slice::from_ref(path))
+			}
+
+			VaqIssue::UntrackedRepo { path } => {
+				if !confirm(&format!("Add {} to config?", path.display()), yes) {
+					continue;
+				}
+				init_vaquera().add(path)
+			}
+
+			VaqIssue::RemotesDiffer { path, .. } => {
+				if !confirm(&format!("Update remotes of {} in config from git?", path.display()), yes) {
+					continue;
+				}
+				init_vaquera().sync_read_remotes_by_path(std::slice::from_ref(path))
+			}
+
+			_ => {
+				eprintln!("Cannot be fixed automatically: {issue}");
+				continue;
+			}
+		};
+
+		if let Err(error) = result {
+			eprintln!("Error: {}", error.message());
+			std::process::exit(1);
+		}
+	}
+}
+
+fn sync_diff(filter: &TagFilter) {
+	let diffs = init_vaquera()
+		.sync_diff(filter)
+		.expect("Sync diff failed");
+
+	if diffs.is_empty() {
+		println!("Remotes in sync");
+		return;
+	}
+
+	for diff in diffs {
+		println!("{}", diff.path.display());
+		for change in &diff.changes {
+			println!("  {change}");
+		}
+	}
+}
+
+fn edit_remotes(edit: &RemoteEdit, tag_args: &[String]) {
+	let filter = TagFilter::from_cli_args(tag_args);
+
+	if let Err(error) = init_vaquera().edit_remotes(&filter, edit) {
+		eprintln!("Error: {}", error.message());
+		std::process::exit(1);
+	}
+}
+
+fn check_remotes(tag_args: &[String]) {
+	let filter = TagFilter::from_cli_args(tag_args);
+	let vaquera = init_vaquera();
+	let repos = vaquera
+		.list(&filter)
+		.expect("Failed to list repositories to check");
+	let settings = vaquera.settings().expect("Failed to read settings");
+
+	let checks = remote_check::check(&repos, &settings);
+
+	for check in &checks {
+		println!("{}", describe_remote_check(check));
+	}
+
+	let problems = checks.iter().filter(|check| !check.is_ok()).count();
+	if problems > 0 {
+		eprintln!("{problems} remotes have problems");
+		std::process::exit(1);
+	}
+}
+
+fn describe_remote_check(check: &RemoteCheck) -> String {
+	let mut line = format!("{} {}: {}", check.repo.display(), check.remote, check.health);
+
+	if let Some(branch) = &check.default_branch {
+		line.push_str(&format!(", default branch {branch}"));
+
+		if check.tracked == Some(false) {
+			line.push_str(&format!(", local {branch} does not track {}/{branch}", check.remote));
+		}
+	}
+
+	line
+}
+
+fn rewrite_remotes(rule: &UrlRewrite, dry_run: bool, tag_args: &[String]) {
+	let filter = TagFilter::from_cli_args(tag_args);
+	let rewritten = init_vaquera()
+		.rewrite_remotes(&filter, rule, dry_run)
+		.expect("Failed to rewrite remotes");
+
+	if rewritten.is_empty() {
+		println!("No remote URLs start with {}", rule.from);
+		return;
+	}
+
+	for remote in rewritten {
+		println!("{remote}");
+	}
+}
+
+fn ask_sync_side(path: &Path, change: &RemoteChange) -> SyncSide {
+	loop {
+		print!("{}: {}. Keep [c]onfig, keep [g]it or [s]kip? ", path.display(), change);
+		std::io::stdout().flush().expect("Failed to flush stdout");
+
+		let mut answer = String::new();
+		if std::io::stdin().read_line(&mut answer).expect("Failed to read answer") == 0 {
+			return SyncSide::Skip;
+		}
+
+		match answer.trim().to_lowercase().as_str() {
+			"c" | "config" => return SyncSide::Config,
+			"g" | "git" => return SyncSide::Git,
+			"s" | "skip" | "" => return SyncSide::Skip,
+			_ => continue,
+		}
+	}
+}
+
+/// A repo in the interactive picker: path, tags and git status, all matched by the fuzzy filter.
+struct PickerEntry {
+	repo: VaqRepo,
+	status: String,
+}
+
+impl std::fmt::Display for PickerEntry {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}  [{}]  {}", self.repo.path.display(), self.repo.tags.join(","), self.status)
+	}
+}
+
+/// Lets the user choose repos in a fuzzy finder. Without a terminal, e.g. in scripts, repo names
+/// or paths are read from stdin instead, one per line, and matched like `vaquera path` does.
+/// Only repos matching `tag_args` are offered.
+fn pick_repos(prompt: &str, multiple: bool, tag_args: &[String]) -> Vec<VaqRepo> {
+	let repos = init_vaquera()
+		.list(&TagFilter::from_cli_args(tag_args))
+		.expect("Failed to list repositories");
+
+	let picked = if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+		pick_in_terminal(prompt, repos, multiple)
+	} else {
+		read_picks(&repos)
+	};
+
+	match picked {
+		Ok(picked) if picked.is_empty() => {
+			eprintln!("Error: No repo selected");
+			std::process::exit(1);
+		}
+		Ok(picked) if !multiple && picked.len() > 1 => {
+			eprintln!("Error: Select only one repo");
+			std::process::exit(1);
+		}
+		Ok(picked) => picked,
+		Err(message) => {
+			eprintln!("Error: {message}");
+			std::process::exit(1);
+		}
+	}
+}
+
+fn pick_in_terminal(prompt: &str, repos: Vec<VaqRepo>, multiple: bool) -> Result<Vec<VaqRepo>, String> {
+	let git = GitImpl {};
+	let entries: Vec<PickerEntry> = repos
+		.into_iter()
+		.map(|repo| PickerEntry { status: describe_status(&git, &repo), repo })
+		.collect();
+
+	let picked = if multiple {
+		MultiSelect::new(prompt, entries)
+			.with_help_message("type to filter, space to select, enter to confirm")
+			.prompt()
+	} else {
+		Select::new(prompt, entries).prompt().map(|entry| vec![entry])
+	};
+
+	picked
+		.map(|entries| entries.into_iter().map(|entry| entry.repo).collect())
+		.map_err(|error| error.to_string())
+}
+
+fn read_picks(repos: &[VaqRepo]) -> Result<Vec<VaqRepo>, String> {
+	let mut picked = Vec::new();
+
+	for line in std::io::stdin().lines() {
+		let line = line.map_err(|error| format!("Failed to read repos from stdin. {error}"))?;
+
+		if !line.trim().is_empty() {
+			let repo = lookup::find_repo(repos, line.trim()).map_err(|error| error.to_string())?;
+			picked.push(repo.clone());
+		}
+	}
+
+	Ok(picked)
+}
+
+/// Branch, dirty state and ahead/behind counts, e.g. `main dirty ↑2`.
+fn describe_status(git: &dyn Git, repo: &VaqRepo) -> String {
+	let Ok(status) = git.status(&repo.path) else {
+		return if repo.path.exists() { "not a git repo" } else { "missing" }.to_string();
+	};
+
+	let mut parts = vec![status.branch.unwrap_or_else(|| "(detached)".to_string())];
+
+	if status.dirty {
+		parts.push("dirty".to_string());
+	}
+	if status.ahead > 0 {
+		parts.push(format!("↑{}", status.ahead));
+	}
+	if status.behind > 0 {
+		parts.push(format!("↓{}", status.behind));
+	}
+
+	parts.join(" ")
+}
+
+/// Asks a yes/no question on stdin; anything but "y"/"yes" means no.
+fn confirm(question: &str, yes: bool) -> bool {
+	if yes {
+		return true;
+	}
+
+	print!("{question} [y/N] ");
+	std::io::stdout().flush().expect("Failed to flush stdout");
+
+	let mut answer = String::new();
+	std::io::stdin()
+		.read_line(&mut answer)
+		.expect("Failed to read answer");
+
+	matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn release_tag(version: &str, options: &ReleaseOptions, tag_args: &[String]) {
+	let filter = TagFilter::from_cli_args(tag_args);
+	let repos = init_vaquera()
+		.list(&filter)
+		.expect("Failed to list repositories for release");
+
+	if let Err(error) = release::tag(&GitImpl {}, &repos, version, options) {
+		match &error {
+			ReleaseError::NotReady(problems) | ReleaseError::PushFailed(problems) => {
+				for (path, problem) in problems {
+					eprintln!("{}: {}", path.display(), problem);
+				}
+			}
+			_ => {}
+		}
+
+		eprintln!("Error: {}", error);
+		std::process::exit(1);
+	}
+}
+
+fn batch(action: &BatchAction) {
+	let vaq_batch = init_batch();
+
+	let result = match action {
+		BatchAction::Apply { branch, message, patch, tag: tag_args, script } => {
+			let change = match patch {
+				Some(patch_path) => BatchChange::Patch(
+					std::fs::read(patch_path).expect("Failed to read patch file"),
+				),
+				None => BatchChange::Script(script.to_owned()),
+			};
+
+			let filter = TagFilter::from_cli_args(tag_args);
+			let repos = init_vaquera()
+				.list(&filter)
+				.expect("Failed to list repositories for batch");
+
+			vaq_batch.apply(branch, message, &change, repos).map(|state| {
+				let failed: Vec<_> = state.repos.iter()
+					.filter_map(|(path, repo_state)| repo_state.error.as_ref().map(|error| (path, error)))
+					.collect();
+
+				for (path, error) in &failed {
+					eprintln!("Warning: {path}: {error}");
+				}
+
+				if !failed.is_empty() {
+					eprintln!("{} repos failed", failed.len());
+					std::process::exit(1);
+				}
+			})
+		}
+
+		BatchAction::Status => vaq_batch.status().map(|state| {
+			println!("Branch: {}", state.branch);
+			println!();
+
+			for (path, repo_state) in &state.repos {
+				match &repo_state.error {
+					Some(error) => println!("{}\t{}\t{}", path, repo_state.status, error),
+					None => println!("{}\t{}", path, repo_state.status),
+				}
+			}
+		}),
+
+		BatchAction::Push { remote } => vaq_batch.push(remote).map(|error_count| {
+			if error_count > 0 {
+				eprintln!("{error_count} repos failed to push");
+				std::process::exit(1);
+			}
+		}),
+
+		BatchAction::Abandon => vaq_batch.abandon(),
+	};
+
+	if let Err(error) = result {
+		eprintln!("Error: {}", error);
+		std::process::exit(1);
+	}
+}
+
 const STATE_FILE: &str = ".vaquera.toml";
+const BATCH_FILE: &str = ".vaquera-batch.toml";
+const HISTORY_FILE: &str = ".vaquera-history.toml";
 
 fn init_vaquera() -> Vaquera {
 	Vaquera::new(
 		Box::new(StorageImpl { path: STATE_FILE }),
 		Box::new(GitImpl {}),
 	)
+	.with_journal(Box::new(StorageImpl { path: HISTORY_FILE }))
+}
+
+fn init_batch() -> VaqBatch {
+	VaqBatch::new(
+		Box::new(StorageImpl { path: BATCH_FILE }),
+		Box::new(GitImpl {}),
+	)
 }
 
 fn add(repo_folders: Vec<String>) -> Result((), VaqError) {
@@ -0,0 +1,24 @@
+# vaquera shell integration: `vq cd <repo>` changes to a repo, anything else runs vaquera.
+# Load it from ~/.bashrc with: eval "$(vaquera shell-init bash)"
+
+vq() {
+	if [ "$1" = cd ]; then
+		shift
+		local dir
+		dir="$(command vaquera path "$@")" && cd "$dir"
+	else
+		command vaquera "$@"
+	fi
+}
+
+_vq() {
+	local IFS=$'\n'
+
+	if [ "$COMP_CWORD" -eq 1 ]; then
+		COMPREPLY=($(compgen -W "cd" -- "${COMP_WORDS[1]}"))
+	elif [ "$COMP_CWORD" -eq 2 ] && [ "${COMP_WORDS[1]}" = cd ]; then
+		COMPREPLY=($(compgen -W "$(command vaquera path --list 2>/dev/null)" -- "${COMP_WORDS[2]}"))
+	fi
+}
+
+complete -F _vq vq
@@ -0,0 +1,14 @@
+# vaquera shell integration: `vq cd <repo>` changes to a repo, anything else runs vaquera.
+# Load it from ~/.config/fish/config.fish with: vaquera shell-init fish | source
+
+function vq --description 'vaquera; `vq cd <repo>` changes to a repo'
+	if test "$argv[1]" = cd
+		set -l dir (command vaquera path $argv[2..-1]); and cd $dir
+	else
+		command vaquera $argv
+	end
+end
+
+complete -c vq -f
+complete -c vq -n __fish_use_subcommand -a cd -d 'Change to a repo'
+complete -c vq -n '__fish_seen_subcommand_from cd' -a '(command vaquera path --list 2>/dev/null)'
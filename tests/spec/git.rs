@@ -2552,3 +2552,1819 @@ url = \"git://example.org/repo3_origin\"
 	let remotes3 = String::from_utf8(output3.stdout).expect("utf8 conversion failed");
 	assert!(remotes3.is_empty());
 }
+
+#[test]
+fn batch_apply_script() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "repo_with_marker", "git://example.org/test_url");
+	add_a_repo(&temp, "repo_without_marker", "git://example.org/test_url2");
+	commit_file(&temp, "repo_with_marker", "marker", "old\n");
+	commit_file(&temp, "repo_without_marker", "README", "readme\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec![
+			"batch",
+			"apply",
+			"--branch",
+			"update-marker",
+			"--message",
+			"Update marker",
+			"--",
+			"if [ -f marker ]; then echo \"$VAQUERA_REPO_NAME\" > marker; fi",
+		])
+		.assert()
+		.success();
+
+	assert_eq!(
+		"update-marker",
+		current_branch(&temp, "repo_with_marker")
+	);
+	assert_eq!("main", current_branch(&temp, "repo_without_marker"));
+	assert_eq!(
+		"repo_with_marker\n",
+		fs::read_to_string(temp.path().join("repo_with_marker/marker")).expect("read marker failed")
+	);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["batch", "status"])
+		.assert()
+		.success()
+		.stdout(predicate::str::contains("Branch: update-marker"))
+		.stdout(predicate::str::contains("repo_with_marker\tchanged"))
+		.stdout(predicate::str::contains("repo_without_marker\tno-op"));
+}
+
+#[test]
+fn batch_abandon() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "test_repo", "git://example.org/test_url");
+	commit_file(&temp, "test_repo", "marker", "old\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec![
+			"batch",
+			"apply",
+			"-b",
+			"update-marker",
+			"-m",
+			"Update marker",
+			"--",
+			"echo new > marker",
+		])
+		.assert()
+		.success();
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["batch", "abandon"])
+		.assert()
+		.success()
+		.stderr(predicate::str::contains("Restored test_repo to main"));
+
+	assert_eq!("main", current_branch(&temp, "test_repo"));
+	assert!(!temp.path().join(".vaquera-batch.toml").exists());
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["batch", "status"])
+		.assert()
+		.failure()
+		.code(1)
+		.stderr(predicate::str::contains("No batch in progress"));
+}
+
+#[test]
+fn batch_apply_without_changes_is_not_recorded() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "test_repo", "git://example.org/test_url");
+	commit_file(&temp, "test_repo", "marker", "old\n");
+
+	for branch in ["first", "second"] {
+		vaquera_executable()
+			.current_dir(&temp)
+			.args(vec!["batch", "apply", "-b", branch, "-m", "Nothing", "--", "true"])
+			.assert()
+			.success();
+
+		assert_eq!("main", current_branch(&temp, "test_repo"));
+		assert!(!temp.path().join(".vaquera-batch.toml").exists());
+	}
+}
+
+#[test]
+fn batch_apply_leaves_dirty_repos_and_existing_branches_alone() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "dirty_repo", "git://example.org/dirty");
+	add_a_repo(&temp, "branched_repo", "git://example.org/branched");
+	add_a_repo(&temp, "failing_repo", "git://example.org/failing");
+
+	for repo in ["dirty_repo", "branched_repo", "failing_repo"] {
+		commit_file(&temp, repo, "marker", "old\n");
+	}
+	fs::write(temp.path().join("dirty_repo/work.txt"), "uncommitted\n").expect("write file failed");
+	Command::new("git")
+		.current_dir(temp.path().join("branched_repo"))
+		.args(vec!["branch", "update"])
+		.output()
+		.expect("git branch failed");
+	commit_file(&temp, "failing_repo", "fail", "");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["batch", "apply", "-b", "update", "-m", "Update", "--", "echo new > marker; touch stray; test ! -e fail"])
+		.assert()
+		.failure()
+		.stderr(predicate::str::contains("dirty_repo: Uncommitted changes"))
+		.stderr(predicate::str::contains("branched_repo: Branch update already exists"))
+		.stderr(predicate::str::contains("failing_repo: Script exited"))
+		.stderr(predicate::str::contains("3 repos failed"));
+
+	for repo in ["dirty_repo", "branched_repo", "failing_repo"] {
+		assert_eq!("main", current_branch(&temp, repo));
+		assert_eq!("old\n", fs::read_to_string(temp.path().join(repo).join("marker")).expect("read marker failed"));
+		assert!(!temp.path().join(repo).join("stray").exists());
+	}
+	assert_eq!("uncommitted\n", fs::read_to_string(temp.path().join("dirty_repo/work.txt")).expect("work kept"));
+
+	// The user's own branch survives
+	let branches = Command::new("git")
+		.current_dir(temp.path().join("branched_repo"))
+		.args(vec!["branch", "--list", "update"])
+		.output()
+		.expect("git branch failed");
+	assert!(String::from_utf8_lossy(&branches.stdout).contains("update"));
+}
+
+fn commit_file(temp: &TempDir, repo_name: &str, file_name: &str, contents: &str) {
+	commit_file_at(temp, repo_name, file_name, contents, "Add file", None);
+}
+
+fn commit_file_at(
+	temp: &TempDir,
+	repo_name: &str,
+	file_name: &str,
+	contents: &str,
+	message: &str,
+	date: Option<&str>,
+) {
+	let path = &temp.path().join(repo_name);
+	fs::write(path.join(file_name), contents).expect("write file failed");
+
+	for args in [
+		vec!["config", "user.name", "Vaquera Test"],
+		vec!["config", "user.email", "test@example.org"],
+		vec!["add", file_name],
+		vec!["commit", "-m", message],
+	] {
+		let mut command = Command::new("git");
+		command.current_dir(path).args(args);
+
+		if let Some(date) = date {
+			command
+				.env("GIT_AUTHOR_DATE", date)
+				.env("GIT_COMMITTER_DATE", date);
+		}
+
+		command.output().expect("git command failed");
+	}
+}
+
+fn current_branch(temp: &TempDir, repo_name: &str) -> String {
+	let output = Command::new("git")
+		.current_dir(temp.path().join(repo_name))
+		.args(vec!["branch", "--show-current"])
+		.output()
+		.expect("git command failed");
+
+	String::from_utf8(output.stdout)
+		.expect("non-utf8 branch name")
+		.trim()
+		.to_string()
+}
+
+#[test]
+fn grep() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "repo_a", "git://example.org/test_url");
+	add_a_repo(&temp, "repo_b", "git://example.org/test_url2");
+	commit_file(&temp, "repo_a", "notes.txt", "nothing here\nfind me\n");
+	commit_file(&temp, "repo_b", "notes.txt", "nothing here either\n");
+	fs::write(temp.path().join("repo_b/untracked.txt"), "find me\n").expect("write file failed");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["grep", "find me"])
+		.assert()
+		.success()
+		.stdout("repo_a/notes.txt:2:find me\n");
+}
+
+#[test]
+fn grep_no_match_succeeds() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "repo_a", "git://example.org/test_url");
+	commit_file(&temp, "repo_a", "notes.txt", "nothing here\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["grep", "absent"])
+		.assert()
+		.success()
+		.stdout("");
+}
+
+#[test]
+fn grep_count_json() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "repo_a", "git://example.org/test_url");
+	commit_file(&temp, "repo_a", "notes.txt", "foo\nbar\nfoo\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["grep", "--count", "--json", "foo"])
+		.assert()
+		.success()
+		.stdout(predicate::str::contains("\"repo\": \"repo_a\""))
+		.stdout(predicate::str::contains("\"path\": \"notes.txt\""))
+		.stdout(predicate::str::contains("\"count\": 2"));
+}
+
+#[test]
+fn log_merges_repos_chronologically() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "repo_a", "git://example.org/test_url");
+	add_a_repo(&temp, "repo_b", "git://example.org/test_url2");
+	commit_file_at(&temp, "repo_a", "a.txt", "1", "First in a", Some("2024-01-01T10:00:00+00:00"));
+	commit_file_at(&temp, "repo_b", "b.txt", "1", "First in b", Some("2024-01-02T10:00:00+00:00"));
+	commit_file_at(&temp, "repo_a", "a.txt", "2", "Second in a", Some("2024-01-03T10:00:00+00:00"));
+
+	let output = vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["log"])
+		.output()
+		.expect("log failed");
+	assert!(output.status.success());
+
+	let stdout = String::from_utf8(output.stdout).expect("non-utf8 output");
+	let summaries: Vec<&str> = stdout
+		.lines()
+		.map(|line| line.split('\t').collect::<Vec<_>>())
+		.map(|fields| {
+			assert_eq!("Vaquera Test", fields[3]);
+			fields[4]
+		})
+		.collect();
+	assert_eq!(vec!["Second in a", "First in b", "First in a"], summaries);
+	assert!(stdout.starts_with("2024-01-03 10:00\trepo_a\t"));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["log", "--since", "2024-01-02T00:00:00Z", "-n", "1"])
+		.assert()
+		.success()
+		.stdout(predicate::str::contains("Second in a"))
+		.stdout(predicate::str::contains("First in b").not());
+}
+
+#[test]
+fn changelog_between_tags() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "repo_a", "git://example.org/test_url");
+	add_a_repo(&temp, "repo_b", "git://example.org/test_url2");
+	commit_file_at(&temp, "repo_a", "a.txt", "1", "chore: initial", None);
+	commit_file_at(&temp, "repo_b", "b.txt", "1", "chore: initial", None);
+	tag_commit(&temp, "repo_a", "v1.0.0");
+	tag_commit(&temp, "repo_b", "v1.0.0");
+	commit_file_at(&temp, "repo_a", "a.txt", "2", "feat(api): add endpoint", None);
+	commit_file_at(&temp, "repo_b", "b.txt", "2", "fix: handle empty input", None);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["changelog", "--from", "v1.0.0"])
+		.assert()
+		.success()
+		.stdout(predicate::str::contains("## Features\n\n### repo_a\n\n- **api:** add endpoint ("))
+		.stdout(predicate::str::contains("## Bug Fixes\n\n### repo_b\n\n- handle empty input ("))
+		.stdout(predicate::str::contains("initial").not());
+}
+
+fn tag_commit(temp: &TempDir, repo_name: &str, tag_name: &str) {
+	Command::new("git")
+		.current_dir(temp.path().join(repo_name))
+		.args(vec!["tag", tag_name])
+		.output()
+		.expect("git command failed");
+}
+
+#[test]
+fn release_tag_and_push() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "repo_a", "git://example.org/test_url");
+	add_a_repo(&temp, "repo_b", "git://example.org/test_url2");
+	commit_file(&temp, "repo_a", "a.txt", "a\n");
+	commit_file(&temp, "repo_b", "b.txt", "b\n");
+	let upstream_a = create_upstream(&temp, "repo_a");
+	let upstream_b = create_upstream(&temp, "repo_b");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["release", "tag", "v1.0.0", "--push"])
+		.assert()
+		.success()
+		.stderr(predicate::str::contains("Tagged repo_a as v1.0.0"))
+		.stderr(predicate::str::contains("Tagged repo_b as v1.0.0"));
+
+	assert_eq!("v1.0.0", list_tags(temp.path().join("repo_a").as_path()));
+	assert_eq!("v1.0.0", list_tags(upstream_a.as_path()));
+	assert_eq!("v1.0.0", list_tags(upstream_b.as_path()));
+}
+
+#[test]
+fn release_tag_refuses_dirty_repo() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "repo_a", "git://example.org/test_url");
+	add_a_repo(&temp, "repo_b", "git://example.org/test_url2");
+	commit_file(&temp, "repo_a", "a.txt", "a\n");
+	commit_file(&temp, "repo_b", "b.txt", "b\n");
+	create_upstream(&temp, "repo_a");
+	create_upstream(&temp, "repo_b");
+	fs::write(temp.path().join("repo_b/b.txt"), "changed\n").expect("write file failed");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["release", "tag", "v1.0.0"])
+		.assert()
+		.failure()
+		.code(1)
+		.stderr(predicate::str::contains("repo_b: uncommitted changes"))
+		.stderr(predicate::str::contains("1 repos are not ready for release"));
+
+	assert_eq!("", list_tags(temp.path().join("repo_a").as_path()));
+	assert_eq!("", list_tags(temp.path().join("repo_b").as_path()));
+}
+
+#[test]
+fn release_tag_fetches_upstream_first() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "repo_a", "git://example.org/test_url");
+	commit_file(&temp, "repo_a", "a.txt", "a\n");
+	let upstream = create_upstream(&temp, "repo_a");
+
+	// Someone else pushes to the upstream
+	Command::new("git")
+		.current_dir(temp.path())
+		.args(vec!["clone", upstream.to_str().expect("non-utf8 temp path"), "other"])
+		.output()
+		.expect("git command failed");
+	commit_file(&temp, "other", "b.txt", "b\n");
+	Command::new("git")
+		.current_dir(temp.path().join("other"))
+		.args(vec!["push", "origin", "main"])
+		.output()
+		.expect("git command failed");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["release", "tag", "v1.0.0", "--dry-run", "--no-fetch"])
+		.assert()
+		.success();
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["release", "tag", "v1.0.0", "--dry-run"])
+		.assert()
+		.failure()
+		.stderr(predicate::str::contains("repo_a: 1 commits behind"))
+		.stderr(predicate::str::contains("1 repos are not ready for release"));
+}
+
+/// Creates a bare repo next to `repo_name`, makes it the repo's `origin` and pushes `main` to it with tracking.
+fn create_upstream(temp: &TempDir, repo_name: &str) -> std::path::PathBuf {
+	let upstream = temp.path().join("upstream").join(format!("{repo_name}.git"));
+	fs::create_dir_all(&upstream).expect("create upstream dir failed");
+
+	Command::new("git")
+		.current_dir(&upstream)
+		.args(vec!["init", "--bare", "--initial-branch", "main"])
+		.output()
+		.expect("git command failed");
+
+	let upstream_url = upstream.to_str().expect("non-utf8 temp path");
+
+	for args in [
+		vec!["remote", "set-url", "origin", upstream_url],
+		vec!["config", "remote.origin.fetch", "+refs/heads/*:refs/remotes/origin/*"],
+		vec!["push", "--set-upstream", "origin", "main"],
+	] {
+		Command::new("git")
+			.current_dir(temp.path().join(repo_name))
+			.args(args)
+			.output()
+			.expect("git command failed");
+	}
+
+	upstream
+}
+
+fn list_tags(path: &std::path::Path) -> String {
+	let output = Command::new("git")
+		.current_dir(path)
+		.args(vec!["tag", "--list"])
+		.output()
+		.expect("git command failed");
+
+	String::from_utf8(output.stdout)
+		.expect("non-utf8 tag name")
+		.trim()
+		.to_string()
+}
+
+#[test]
+fn doctor_reports_drift() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "tracked_repo", "git://example.org/test_url");
+	create_git_repo(&temp, "untracked_repo", "git://example.org/untracked");
+	fs::create_dir_all(temp.path().join("plain_folder")).expect("create dir failed");
+
+	Command::new("git")
+		.current_dir(temp.path().join("tracked_repo"))
+		.args(vec!["config", "remote.origin.url", "git://example.org/changed_url"])
+		.output()
+		.expect("git command failed");
+
+	let initial_state_toml = read_vaquera_state_toml(&temp)
+		+ "
+[[repos]]
+path = \"missing_repo\"
+tags = []
+
+[repos.remotes]
+
+[[repos]]
+path = \"plain_folder\"
+tags = []
+
+[repos.remotes]
+";
+	write_vaquera_state_toml(&temp, &initial_state_toml);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["doctor"])
+		.assert()
+		.failure()
+		.code(1)
+		.stdout(predicate::str::contains("missing_repo: repo folder missing"))
+		.stdout(predicate::str::contains("plain_folder: not a git repository"))
+		.stdout(predicate::str::contains(
+			"tracked_repo: remotes differ from config (`origin` is git://example.org/test_url in config but git://example.org/changed_url in git)",
+		))
+		.stdout(predicate::str::contains("untracked_repo: git repository not in config"))
+		.stderr(predicate::str::contains("4 problems found"));
+}
+
+#[test]
+fn doctor_reports_unreadable_remotes() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "broken_repo", "git://example.org/test_url");
+	fs::write(temp.path().join("broken_repo/.git/config"), "[remote \"origin\"\n\turl = ")
+		.expect("write git config failed");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["doctor"])
+		.assert()
+		.failure()
+		.code(1)
+		.stdout(predicate::str::contains("broken_repo: cannot read remotes from git"))
+		.stderr(predicate::str::contains("1 problems found"));
+}
+
+#[test]
+fn doctor_fix() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "untracked_repo", "git://example.org/untracked");
+
+	let initial_state_toml = "[[repos]]
+path = \"missing_repo\"
+tags = []
+
+[repos.remotes]
+";
+	write_vaquera_state_toml(&temp, initial_state_toml);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["doctor", "--fix", "--yes"])
+		.assert()
+		.success();
+
+	let expected_toml = "[[repos]]
+path = \"untracked_repo\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git://example.org/untracked\"
+";
+	assert_eq!(expected_toml, read_vaquera_state_toml(&temp));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["doctor"])
+		.assert()
+		.success()
+		.stdout("No problems found\n");
+}
+
+#[test]
+fn prune() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "existing_repo", "git://example.org/test_url");
+	fs::create_dir_all(temp.path().join("plain_folder")).expect("create dir failed");
+
+	let initial_state_toml = read_vaquera_state_toml(&temp)
+		+ "
+[[repos]]
+path = \"missing_repo\"
+tags = []
+
+[repos.remotes]
+
+[[repos]]
+path = \"plain_folder\"
+tags = []
+
+[repos.remotes]
+";
+	write_vaquera_state_toml(&temp, &initial_state_toml);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["prune", "--dry-run"])
+		.assert()
+		.success()
+		.stdout("Would remove missing_repo\n");
+	assert_eq!(initial_state_toml, read_vaquera_state_toml(&temp));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["prune", "--not-git"])
+		.assert()
+		.success()
+		.stdout("Removed missing_repo\nRemoved plain_folder\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["list"])
+		.assert()
+		.success()
+		.stdout("existing_repo\n");
+}
+
+#[test]
+fn sync_diff_and_write_remotes_exactly() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "test_repo", "git://example.org/old_url");
+
+	for args in [
+		vec!["remote", "add", "stale", "git://example.org/stale_url"],
+		vec!["remote", "set-url", "--push", "origin", "git://example.org/push_url"],
+	] {
+		Command::new("git")
+			.current_dir(temp.path().join("test_repo"))
+			.args(args)
+			.output()
+			.expect("git command failed");
+	}
+
+	let initial_state_toml = "[[repos]]
+path = \"test_repo\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git://example.org/new_url\"
+
+[repos.remotes.upstream]
+name = \"upstream\"
+url = \"git://example.org/upstream_url\"
+";
+	write_vaquera_state_toml(&temp, initial_state_toml);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["sync", "--diff"])
+		.assert()
+		.success()
+		.stdout(
+			"test_repo
+  ~ origin git://example.org/old_url -> git://example.org/new_url
+  ~ origin (push) git://example.org/push_url -> git://example.org/new_url
+  - stale git://example.org/stale_url
+  + upstream git://example.org/upstream_url
+",
+		);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["sync", "--write-remotes", "--prune", "--update-urls"])
+		.assert()
+		.success();
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["sync", "--diff"])
+		.assert()
+		.success()
+		.stdout("Remotes in sync\n");
+}
+
+#[test]
+fn sync_keeps_separate_push_urls() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "test_repo", "git://example.org/test_url");
+
+	let initial_state_toml = "[[repos]]
+path = \"test_repo\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git://example.org/test_url\"
+push_url = \"git://example.org/push_url\"
+
+[repos.remotes.upstream]
+name = \"upstream\"
+url = \"git://example.org/upstream_url\"
+push_url = \"git://example.org/upstream_push_url\"
+";
+	write_vaquera_state_toml(&temp, initial_state_toml);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["sync", "--diff"])
+		.assert()
+		.success()
+		.stdout(
+			"test_repo\n  ~ origin (push) git://example.org/test_url -> git://example.org/push_url\n  + upstream git://example.org/upstream_url\n",
+		);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["sync", "--write-remotes", "--update-urls"])
+		.assert()
+		.success();
+
+	let push_url = Command::new("git")
+		.current_dir(temp.path().join("test_repo"))
+		.args(vec!["config", "remote.origin.pushurl"])
+		.output()
+		.expect("git command failed");
+	assert_eq!(String::from_utf8_lossy(&push_url.stdout).trim(), "git://example.org/push_url");
+
+	// The remote missing from git gets its push URL along with it
+	let upstream_push_url = Command::new("git")
+		.current_dir(temp.path().join("test_repo"))
+		.args(vec!["config", "remote.upstream.pushurl"])
+		.output()
+		.expect("git command failed");
+	assert_eq!(String::from_utf8_lossy(&upstream_push_url.stdout).trim(), "git://example.org/upstream_push_url");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["sync", "--read-remotes"])
+		.assert()
+		.success();
+
+	let state_toml = read_vaquera_state_toml(&temp);
+	assert!(state_toml.contains("push_url = \"git://example.org/push_url\""));
+	assert!(state_toml.contains("push_url = \"git://example.org/upstream_push_url\""));
+}
+
+#[test]
+fn remotes_rewrite_ssh_to_https() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "test_repo", "git@git.old.corp:team/test_repo.git");
+
+	let initial_state_toml = "[[repos]]
+path = \"test_repo\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git@git.old.corp:team/test_repo.git\"
+";
+	write_vaquera_state_toml(&temp, initial_state_toml);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remotes", "rewrite", "--from", "git@git.old.corp:", "--to", "https://git.new.corp/"])
+		.assert()
+		.success()
+		.stdout(
+			"test_repo origin (config): git@git.old.corp:team/test_repo.git -> https://git.new.corp/team/test_repo.git
+test_repo origin (git): git@git.old.corp:team/test_repo.git -> https://git.new.corp/team/test_repo.git
+",
+		);
+
+	let expected_toml = "[[repos]]
+path = \"test_repo\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"https://git.new.corp/team/test_repo.git\"
+";
+	assert_eq!(expected_toml, read_vaquera_state_toml(&temp));
+
+	assert_eq!("https://git.new.corp/team/test_repo.git\n", remote_url(&temp, "test_repo", "origin"));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remotes", "rewrite", "--from", "git@git.old.corp:", "--to", "https://git.new.corp/"])
+		.assert()
+		.success()
+		.stdout("No remote URLs start with git@git.old.corp:\n");
+}
+
+#[test]
+fn clone_applies_url_rewrites() {
+	let temp = temp_folder();
+	create_local_repo(&temp, "source1");
+
+	let initial_state_toml = "[[url_rewrites]]
+from = \"moved/\"
+to = \"\"
+
+[[repos]]
+path = \"repo1\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"moved/source1\"
+";
+	write_vaquera_state_toml(&temp, initial_state_toml);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["clone"])
+		.assert()
+		.success();
+
+	assert!(temp.path().join("repo1").exists());
+
+	// The rewritten URL in git is not drift
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["sync", "--diff"])
+		.assert()
+		.success()
+		.stdout("Remotes in sync\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["doctor"])
+		.assert()
+		.stdout(predicate::str::contains("repo1:").not());
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["sync", "--read-remotes"])
+		.assert()
+		.success();
+
+	assert!(read_vaquera_state_toml(&temp).contains("url = \"moved/source1\""));
+
+	// Settings survive saving the repo list
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["tag", "foo", "repo1"])
+		.assert()
+		.success();
+
+	assert!(read_vaquera_state_toml(&temp).starts_with("[[url_rewrites]]\nfrom = \"moved/\"\nto = \"\"\n"));
+}
+
+#[test]
+fn remote_add_rename_set_url_remove() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "api", "git@github.com:team/api.git");
+	create_git_repo(&temp, "web", "git@github.com:team/web.git");
+
+	let initial_state_toml = "[[repos]]
+path = \"api\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git@github.com:team/api.git\"
+
+[[repos]]
+path = \"web\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git@github.com:team/web.git\"
+";
+	write_vaquera_state_toml(&temp, initial_state_toml);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remote", "add", "fork", "git@github.com:myfork/{name}.git"])
+		.assert()
+		.success()
+		.stderr(predicate::str::contains("Added remote fork to api"))
+		.stderr(predicate::str::contains("Added remote fork to web"));
+
+	assert_eq!("git@github.com:myfork/web.git\n", remote_url(&temp, "web", "fork"));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remote", "add", "fork", "git@github.com:other/{name}.git"])
+		.assert()
+		.failure()
+		.stderr(predicate::str::contains("Warning: api: remote fork already exists"));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remote", "rename", "fork", "mine"])
+		.assert()
+		.success();
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remote", "set-url", "mine", "https://github.com/myfork/{name}", "--tag", "nothing"])
+		.assert()
+		.success();
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remote", "set-url", "mine", "https://github.com/myfork/{name}"])
+		.assert()
+		.success();
+
+	assert_eq!("https://github.com/myfork/api\n", remote_url(&temp, "api", "mine"));
+
+	let expected_toml = "[[repos]]
+path = \"api\"
+tags = []
+
+[repos.remotes.mine]
+name = \"mine\"
+url = \"https://github.com/myfork/api\"
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git@github.com:team/api.git\"
+
+[[repos]]
+path = \"web\"
+tags = []
+
+[repos.remotes.mine]
+name = \"mine\"
+url = \"https://github.com/myfork/web\"
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git@github.com:team/web.git\"
+";
+	assert_eq!(expected_toml, read_vaquera_state_toml(&temp));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remote", "remove", "mine"])
+		.assert()
+		.success();
+
+	assert_eq!(initial_state_toml, read_vaquera_state_toml(&temp));
+	assert_eq!("origin\n", remote_names(&temp, "web"));
+}
+
+fn remote_url(temp: &TempDir, repo_name: &str, remote_name: &str) -> String {
+	let output = Command::new("git")
+		.current_dir(temp.path().join(repo_name))
+		.args(vec!["remote", "get-url", remote_name])
+		.output()
+		.expect("git remote get-url failed");
+
+	String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn remote_names(temp: &TempDir, repo_name: &str) -> String {
+	let output = Command::new("git")
+		.current_dir(temp.path().join(repo_name))
+		.args(vec!["remote"])
+		.output()
+		.expect("git remote failed");
+
+	String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn fork_setup() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "api", "git://example.org/test_url");
+	commit_file(&temp, "api", "README.md", "api");
+	let canonical = create_upstream(&temp, "api");
+	let canonical_url = canonical.to_str().expect("non-utf8 temp path");
+	let fork_url = temp.path().join("me").join("api.git");
+	let fork_url = fork_url.to_str().expect("non-utf8 temp path");
+
+	let initial_state_toml = format!("[[repos]]
+path = \"api\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"{canonical_url}\"
+");
+	write_vaquera_state_toml(&temp, &initial_state_toml);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["fork", "setup", "--owner", "me"])
+		.assert()
+		.success()
+		.stderr(predicate::str::contains("main now tracks upstream/main in api"));
+
+	let expected_toml = format!("[[repos]]
+path = \"api\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"{fork_url}\"
+
+[repos.remotes.upstream]
+name = \"upstream\"
+url = \"{canonical_url}\"
+");
+	assert_eq!(expected_toml, read_vaquera_state_toml(&temp));
+	assert_eq!(format!("{fork_url}\n"), remote_url(&temp, "api", "origin"));
+	assert_eq!(format!("{canonical_url}\n"), remote_url(&temp, "api", "upstream"));
+
+	let output = Command::new("git")
+		.current_dir(temp.path().join("api"))
+		.args(vec!["rev-parse", "--abbrev-ref", "main@{upstream}"])
+		.output()
+		.expect("git rev-parse failed");
+	assert_eq!("upstream/main\n", String::from_utf8_lossy(&output.stdout));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["fork", "setup", "--owner", "me"])
+		.assert()
+		.success()
+		.stderr(predicate::str::contains("api already set up as fork of me"));
+}
+
+#[test]
+fn remotes_check() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "api", "git://example.org/test_url");
+	commit_file(&temp, "api", "README.md", "api");
+	let upstream = create_upstream(&temp, "api");
+	let upstream_url = upstream.to_str().expect("non-utf8 temp path");
+	let gone_url = temp.path().join("gone.git");
+	let gone_url = gone_url.to_str().expect("non-utf8 temp path");
+
+	let initial_state_toml = format!("[[repos]]
+path = \"api\"
+tags = []
+
+[repos.remotes.gone]
+name = \"gone\"
+url = \"{gone_url}\"
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"{upstream_url}\"
+");
+	write_vaquera_state_toml(&temp, &initial_state_toml);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remotes", "check"])
+		.assert()
+		.failure()
+		.stdout("api gone: not found\napi origin: ok, default branch main\n")
+		.stderr(predicate::str::contains("1 remotes have problems"));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remote", "remove", "gone"])
+		.assert()
+		.success();
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remotes", "check"])
+		.assert()
+		.success()
+		.stdout("api origin: ok, default branch main\n");
+
+	// Only the remote main tracks is checked for tracking
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remote", "add", "mirror", upstream_url])
+		.assert()
+		.success();
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remotes", "check"])
+		.assert()
+		.success()
+		.stdout("api mirror: ok, default branch main\napi origin: ok, default branch main\n");
+}
+
+#[test]
+fn remotes_check_uses_rewritten_urls() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "api", "git://example.org/test_url");
+	commit_file(&temp, "api", "README.md", "api");
+	create_upstream(&temp, "api");
+	let temp_path = temp.path().to_str().expect("non-utf8 temp path");
+
+	let initial_state_toml = format!("[[url_rewrites]]
+from = \"{temp_path}/moved/\"
+to = \"{temp_path}/upstream/\"
+
+[[repos]]
+path = \"api\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"{temp_path}/moved/api.git\"
+");
+	write_vaquera_state_toml(&temp, &initial_state_toml);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remotes", "check"])
+		.assert()
+		.success()
+		.stdout("api origin: ok, default branch main\n");
+}
+
+#[test]
+fn relayout() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "api", "git@github.com:team/api.git");
+
+	let initial_state_toml = "layout = \"{host}/{owner}/{name}\"
+
+[[repos]]
+path = \"api\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git@github.com:team/api.git\"
+
+[[repos]]
+path = \"not_cloned\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"https://gitlab.com/group/sub/web.git\"
+";
+	write_vaquera_state_toml(&temp, initial_state_toml);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["relayout", "--dry-run"])
+		.assert()
+		.success()
+		.stdout("Would move api to github.com/team/api\nWould move not_cloned to gitlab.com/group/sub/web\n");
+
+	assert_eq!(initial_state_toml, read_vaquera_state_toml(&temp));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["relayout"])
+		.assert()
+		.success();
+
+	assert!(temp.path().join("github.com/team/api/.git").exists());
+	assert!(!temp.path().join("api").exists());
+
+	let state_toml = read_vaquera_state_toml(&temp);
+	assert!(state_toml.contains("path = \"github.com/team/api\""));
+	assert!(state_toml.contains("path = \"gitlab.com/group/sub/web\""));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["relayout"])
+		.assert()
+		.success()
+		.stdout("All repos already follow the layout\n");
+}
+
+#[test]
+fn relayout_checks_targets_before_moving() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "api", "git@github.com:team/api.git");
+	create_git_repo(&temp, "api_copy", "git@github.com:team/api.git");
+
+	let initial_state_toml = "layout = \"{host}/{owner}/{name}\"
+
+[[repos]]
+path = \"api\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git@github.com:team/api.git\"
+
+[[repos]]
+path = \"api_copy\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git@github.com:team/api.git\"
+";
+	write_vaquera_state_toml(&temp, initial_state_toml);
+
+	for args in [vec!["relayout", "--dry-run"], vec!["relayout"]] {
+		vaquera_executable()
+			.current_dir(&temp)
+			.args(args)
+			.assert()
+			.failure()
+			.stderr(predicate::str::contains("Cannot move api api_copy to github.com/team/api: same target"))
+			.stderr(predicate::str::contains("2 repos cannot be moved, nothing moved"));
+	}
+
+	assert!(temp.path().join("api/.git").exists());
+	assert!(temp.path().join("api_copy/.git").exists());
+	assert_eq!(initial_state_toml, read_vaquera_state_toml(&temp));
+}
+
+#[test]
+fn move_repos_moves_back_when_one_fails() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "api", "git://example.org/api");
+	add_a_repo(&temp, "web", "git://example.org/web");
+	fs::create_dir_all(temp.path().join("libs/web")).expect("create dir failed");
+	let initial_state_toml = read_vaquera_state_toml(&temp);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["move", "repo", "api", "web", "libs"])
+		.assert()
+		.failure()
+		.stderr(predicate::str::contains("libs/web already exists"));
+
+	assert!(temp.path().join("api/.git").exists());
+	assert!(!temp.path().join("libs/api").exists());
+	assert_eq!(initial_state_toml, read_vaquera_state_toml(&temp));
+}
+
+#[test]
+fn move_repos_by_name_into_folder_keeps_worktrees() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "api", "git://example.org/api");
+	add_a_repo(&temp, "tools/web", "git://example.org/web");
+	commit_file(&temp, "api", "README.md", "api");
+
+	Command::new("git")
+		.current_dir(temp.path().join("api"))
+		.args(vec!["worktree", "add", "-b", "feature", "../api-feature"])
+		.output()
+		.expect("git worktree add failed");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["move", "repo", "api", "web", "libs"])
+		.assert()
+		.success()
+		.stderr(predicate::str::contains("Moved api to libs/api"))
+		.stderr(predicate::str::contains("Moved tools/web to libs/web"));
+
+	assert!(temp.path().join("libs/api/README.md").exists());
+	assert!(temp.path().join("libs/web").exists());
+	assert!(!temp.path().join("tools/web").exists());
+
+	let state_toml = read_vaquera_state_toml(&temp);
+	assert!(state_toml.contains("path = \"libs/api\""));
+	assert!(state_toml.contains("path = \"libs/web\""));
+
+	// The worktree still finds its repo, and the repo its worktree
+	let status = Command::new("git")
+		.current_dir(temp.path().join("api-feature"))
+		.args(vec!["status"])
+		.output()
+		.expect("git status failed");
+	assert!(status.status.success());
+
+	let output = Command::new("git")
+		.current_dir(temp.path().join("libs/api"))
+		.args(vec!["worktree", "list", "--porcelain"])
+		.output()
+		.expect("git worktree list failed");
+	let worktrees = String::from_utf8_lossy(&output.stdout);
+	assert!(worktrees.contains("api-feature"));
+	assert!(!worktrees.contains("prunable"));
+}
+
+#[test]
+fn move_repo_refuses_existing_target() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "api", "git://example.org/api");
+	add_a_repo(&temp, "web", "git://example.org/web");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["move", "repo", "api", "web"])
+		.assert()
+		.failure()
+		.stderr(predicate::str::contains("Target web already exists"));
+
+	assert!(temp.path().join("api").exists());
+}
+
+#[test]
+fn history_and_undo() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "api", "git://example.org/api");
+	add_a_repo(&temp, "web", "git://example.org/web");
+	let two_repos_toml = read_vaquera_state_toml(&temp);
+
+	tag_repo(&temp, "api", "backend");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["remove", "api", "web"])
+		.assert()
+		.success();
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["history"])
+		.assert()
+		.success()
+		.stdout(predicate::str::is_match(
+			"^  1  [0-9-]+ [0-9:]+  remove api web\n  2  [0-9-]+ [0-9:]+  tag backend api\n  3  [0-9-]+ [0-9:]+  add web\n  4  [0-9-]+ [0-9:]+  add api\n$",
+		).expect("valid regex"));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["undo", "2"])
+		.assert()
+		.success()
+		.stdout("Undid remove api web\nUndid tag backend api\n");
+
+	assert_eq!(two_repos_toml, read_vaquera_state_toml(&temp));
+
+	// Changes made by hand are not overwritten
+	write_vaquera_state_toml(&temp, "repos = []\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["undo"])
+		.assert()
+		.failure()
+		.stderr(predicate::str::contains("was changed outside vaquera"));
+}
+
+#[test]
+fn undo_moves_folder_back() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "api", "git://example.org/api");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["move", "repo", "api", "services/api"])
+		.assert()
+		.success();
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["undo"])
+		.assert()
+		.success()
+		.stdout("Undid move api services/api\n");
+
+	assert!(temp.path().join("api/.git").exists());
+	assert!(!temp.path().join("services/api").exists());
+	assert!(read_vaquera_state_toml(&temp).contains("path = \"api\""));
+}
+
+#[test]
+fn path_finds_repo_from_anywhere_in_workspace() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "services/service-api", "git://example.org/api");
+	add_a_repo(&temp, "services/web", "git://example.org/web");
+	let root = fs::canonicalize(temp.path()).expect("canonical temp path");
+
+	for query in ["service-api", "services/service-api", "API", "srvapi"] {
+		vaquera_executable()
+			.current_dir(temp.path().join("services/web"))
+			.args(vec!["path", query])
+			.assert()
+			.success()
+			.stdout(format!("{}\n", root.join("services/service-api").display()));
+	}
+
+	vaquera_executable()
+		.current_dir(temp.path().join("services"))
+		.args(vec!["path"])
+		.assert()
+		.success()
+		.stdout(format!("{}\n", root.display()));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["path", "--list"])
+		.assert()
+		.success()
+		.stdout("service-api\nweb\n");
+}
+
+#[test]
+fn path_reports_ambiguous_and_missing_repos() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "services/api", "git://example.org/api");
+	add_a_repo(&temp, "services/web", "git://example.org/web");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["path", "services"])
+		.assert()
+		.failure()
+		.stderr("Error: 'services' matches several repos: services/api, services/web\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["path", "docs"])
+		.assert()
+		.failure()
+		.stderr("Error: No repo matches 'docs'\n");
+}
+
+#[test]
+fn shell_init() {
+	for (shell, definition) in [
+		("bash", "vq() {"),
+		("zsh", "compdef _vq vq"),
+		("fish", "function vq"),
+		("nu", "def --env \"vq cd\""),
+	] {
+		vaquera_executable()
+			.args(vec!["shell-init", shell])
+			.assert()
+			.success()
+			.stdout(predicate::str::contains(definition).and(predicate::str::contains("vaquera path")));
+	}
+}
+
+#[test]
+fn completions_script() {
+	vaquera_executable()
+		.args(vec!["completions", "bash"])
+		.assert()
+		.success()
+		.stdout(predicate::str::contains("COMPLETE=").and(predicate::str::contains("complete ")));
+}
+
+#[test]
+fn completions_of_repos_tags_and_remotes() {
+	let temp = temp_folder();
+	add_a_repo_with_tags(&temp, "services/api", "git://example.org/api", vec!["backend"]);
+	add_a_repo_with_tags(&temp, "web", "git://example.org/web", vec!["frontend"]);
+
+	let complete_in = |dir: &std::path::Path, args: &[&str]| {
+		let output = vaquera_executable()
+			.current_dir(dir)
+			.env("COMPLETE", "fish")
+			.args(["--", "vaquera"])
+			.args(args)
+			.output()
+			.expect("Failed to complete");
+		assert!(output.status.success());
+		String::from_utf8(output.stdout).expect("utf8 completions")
+	};
+	let complete = |args: &[&str]| complete_in(temp.path(), args);
+
+	assert_eq!("services/api\nweb\napi\tservices/api\n", complete(&["tag", "backend", ""]));
+	assert_eq!("backend\n", complete(&["list", "--tag", "back"]));
+	assert_eq!("backend,frontend\n", complete(&["list", "--tag", "backend,"]));
+	assert_eq!("origin\n", complete(&["remotes", "rename", ""]));
+
+	// From a folder inside the workspace
+	let services = temp.path().join("services");
+	assert_eq!("backend\n", complete_in(&services, &["list", "--tag", "back"]));
+	assert_eq!("origin\n", complete_in(&services, &["remotes", "rename", ""]));
+}
+
+#[test]
+fn interactive_reads_repos_from_stdin_without_terminal() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "services/service-api", "git://example.org/api");
+	add_a_repo(&temp, "web", "git://example.org/web");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["tag", "backend", "--interactive"])
+		.write_stdin("srvapi\nweb\n")
+		.assert()
+		.success();
+
+	let state_toml = read_vaquera_state_toml(&temp);
+	assert_eq!(2, state_toml.matches("tags = [\"backend\"]").count());
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["move", "repo", "-i", "libs"])
+		.write_stdin("web\n")
+		.assert()
+		.success();
+
+	assert!(temp.path().join("libs/web/.git").exists());
+
+	// Only repos matching --tag are offered
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["tag", "frontend", "libs/web"])
+		.assert()
+		.success();
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["show", "-i", "--tag", "frontend"])
+		.write_stdin("srvapi\n")
+		.assert()
+		.failure();
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["show", "-i", "--tag", "frontend"])
+		.write_stdin("web\n")
+		.assert()
+		.success()
+		.stdout(predicate::str::contains("git://example.org/web"));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["show", "-i"])
+		.write_stdin("")
+		.assert()
+		.failure()
+		.stderr("Error: No repo selected\n");
+}
+
+#[test]
+fn ui_needs_terminal() {
+	let temp = temp_folder();
+	add_a_repo(&temp, "api", "git://example.org/api");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["ui"])
+		.assert()
+		.failure()
+		.stderr("Error: ui needs a terminal\n");
+}
+
+#[test]
+fn run_custom_command() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "api", "git://example.org/api");
+	create_git_repo(&temp, "web", "git://example.org/web");
+	fs::create_dir_all(temp.path().join("api/docs")).expect("create docs folder");
+
+	write_vaquera_state_toml(&temp, "[commands.greet]
+run = \"echo $GREETING from $(basename $(pwd))\"
+description = \"Say hello from the docs\"
+tags = [\"backend\"]
+dir = \"docs\"
+env = { GREETING = \"hello\" }
+
+[[repos]]
+path = \"api\"
+tags = [\"backend\"]
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git://example.org/api\"
+
+[[repos]]
+path = \"web\"
+tags = []
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git://example.org/web\"
+");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["run"])
+		.assert()
+		.success()
+		.stdout("greet\tSay hello from the docs\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["run", "greet", "--oneline"])
+		.assert()
+		.success()
+		.stdout("api/docs\thello from docs\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["greet", "--oneline", "--tag", "backend"])
+		.assert()
+		.success()
+		.stdout("api/docs\thello from docs\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["gret"])
+		.assert()
+		.failure()
+		.stderr(predicate::str::starts_with("error: unrecognized subcommand 'gret'"));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["stauts"])
+		.assert()
+		.failure()
+		.stderr(predicate::str::contains("unrecognized subcommand 'stauts'"))
+		.stderr(predicate::str::contains("a similar subcommand exists: 'status'"));
+}
+
+#[test]
+fn hooks_run_after_clone_and_around_exec() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "source", "git://example.org/test_url");
+	commit_file(&temp, "source", "README.md", "api");
+	let upstream = create_upstream(&temp, "source");
+
+	write_vaquera_state_toml(&temp, "[hooks]
+post-clone = \"echo \\\"$VAQUERA_HOOK $VAQUERA_REPO_NAME $VAQUERA_REPO_TAGS\\\" > hook.txt\"
+pre-exec = \"test ! -e skip\"
+post-exec = \"echo \\\"exit $VAQUERA_EXIT_CODE\\\" >> hook.txt\"
+");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["clone", upstream.to_str().expect("utf8 path"), "services/api", "--tag", "backend"])
+		.assert()
+		.success();
+
+	assert_eq!(
+		"post-clone api backend\n",
+		fs::read_to_string(temp.path().join("services/api/hook.txt")).expect("post-clone hook ran")
+	);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["exec", "--oneline", "--", "echo", "hello"])
+		.assert()
+		.success()
+		.stdout("services/api\thello\n");
+
+	assert_eq!(
+		"post-clone api backend\nexit 0\n",
+		fs::read_to_string(temp.path().join("services/api/hook.txt")).expect("post-exec hook ran")
+	);
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["exec", "--oneline", "--", "exit", "3"])
+		.assert()
+		.failure();
+
+	assert_eq!(
+		"post-clone api backend\nexit 0\nexit 3\n",
+		fs::read_to_string(temp.path().join("services/api/hook.txt")).expect("post-exec hook ran")
+	);
+
+	fs::write(temp.path().join("services/api/skip"), "").expect("create skip file");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["exec", "--oneline", "--", "echo", "not run"])
+		.assert()
+		.failure()
+		.stdout("")
+		.stderr(predicate::str::contains("pre-exec hook failed in services/api"));
+}
+
+#[test]
+fn exec_expands_repo_placeholders_and_env() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "api", "git://example.org/api");
+	commit_file(&temp, "api", "README.md", "api");
+
+	write_vaquera_state_toml(&temp, "[[repos]]
+path = \"api\"
+tags = [\"backend\"]
+
+[repos.remotes.origin]
+name = \"origin\"
+url = \"git://example.org/api\"
+
+[repos.meta]
+owner = \"payments\"
+");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["exec", "--oneline", "--", "echo", "{name} {tags} {remote.origin} {meta.owner} {branch} {unknown}"])
+		.assert()
+		.success()
+		.stdout("api\tapi backend git://example.org/api payments main {unknown}\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["exec", "--oneline", "--", "echo $VAQUERA_REPO_NAME $VAQUERA_REPO_TAGS $VAQUERA_REPO_REMOTE_ORIGIN"])
+		.assert()
+		.success()
+		.stdout("api\tapi backend git://example.org/api\n");
+}
+
+#[test]
+fn exec_without_shell_passes_args_untouched() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "repo_a", "git://example.org/test_url");
+
+	write_vaquera_state_toml(&temp, "[[repos]]
+path = \"repo_a\"
+tags = []
+");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["exec", "--oneline", "--no-shell", "--", "echo", "$HOME", "a  b"])
+		.assert()
+		.success()
+		.stdout("repo_a\t$HOME a  b\n");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["exec", "--oneline", "--no-shell", "--", "echo $HOME"])
+		.assert()
+		.failure()
+		.stderr(predicate::str::contains("Warning: Failed to run command in repo_a"))
+		.stderr(predicate::str::contains("1 commands exited with non-zero status code"));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["exec", "--no-shell", "--shell", "bash", "--", "true"])
+		.assert()
+		.failure()
+		.stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn exec_timeout_fail_fast_and_retry() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "repo_a", "git://example.org/test_url");
+	create_git_repo(&temp, "repo_b", "git://example.org/test_url");
+
+	write_vaquera_state_toml(&temp, "[[repos]]
+path = \"repo_a\"
+tags = []
+
+[[repos]]
+path = \"repo_b\"
+tags = []
+");
+
+	let started = std::time::Instant::now();
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["exec", "--oneline", "--timeout", "300ms", "--", "sleep 30 & wait"])
+		.assert()
+		.failure()
+		.stdout("repo_a\tCommand timed out after 300ms, killed\nrepo_b\tCommand timed out after 300ms, killed\n")
+		.stderr(predicate::str::contains("2 commands timed out"));
+	assert!(started.elapsed() < std::time::Duration::from_secs(10));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["exec", "--oneline", "--fail-fast", "--", "false"])
+		.assert()
+		.failure()
+		.stdout("repo_a\t\n")
+		.stderr(predicate::str::contains("1 commands exited with non-zero status code"))
+		.stderr(predicate::str::contains("1 repos not run after a failure"));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["exec", "--oneline", "--retry", "2", "--retry-delay", "0s", "--", "test -e tried || { touch tried; false; }"])
+		.assert()
+		.success()
+		.stdout("repo_a\t\nrepo_b\t\n")
+		.stderr(predicate::str::contains("Warning: Command exited with code 1 in repo_a, retry 1 of 2"))
+		.stderr(predicate::str::contains("2 commands succeeded after retrying"));
+}
+
+#[cfg(unix)]
+#[test]
+fn exec_timeout_forwards_interrupt() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "repo_a", "git://example.org/test_url");
+
+	write_vaquera_state_toml(&temp, "[[repos]]
+path = \"repo_a\"
+tags = []
+");
+
+	let mut vaquera = Command::new(assert_cmd::cargo::cargo_bin("vaquera"))
+		.current_dir(&temp)
+		.args(vec!["exec", "--timeout", "60s", "--", "echo $$ > sleep.pid; exec sleep 60"])
+		.stdout(std::process::Stdio::null())
+		.stderr(std::process::Stdio::null())
+		.spawn()
+		.expect("failed to start vaquera");
+
+	let pid_file = temp.path().join("repo_a/sleep.pid");
+	let wait_until = |done: &dyn Fn() -> bool| {
+		let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+		while !done() && std::time::Instant::now() < deadline {
+			std::thread::sleep(std::time::Duration::from_millis(20));
+		}
+		done()
+	};
+	assert!(wait_until(&|| fs::read_to_string(&pid_file).is_ok_and(|pid| pid.ends_with('\n'))));
+
+	Command::new("kill")
+		.args(vec!["-INT", &vaquera.id().to_string()])
+		.status()
+		.expect("kill failed");
+	assert!(!vaquera.wait().expect("vaquera did not exit").success());
+
+	// The command in its own process group got the interrupt too
+	let pid = fs::read_to_string(&pid_file).expect("read pid failed");
+	let sleep_running = || {
+		Command::new("kill")
+			.args(vec!["-0", pid.trim()])
+			.stderr(std::process::Stdio::null())
+			.status()
+			.is_ok_and(|status| status.success())
+	};
+	assert!(wait_until(&|| !sleep_running()));
+}
+
+#[test]
+fn exec_logs_summary_and_reports() {
+	let temp = temp_folder();
+	create_git_repo(&temp, "repo_a", "git://example.org/test_url");
+	create_git_repo(&temp, "repo_b", "git://example.org/test_url");
+	fs::write(temp.path().join("repo_b/fail"), "").expect("create fail marker");
+
+	write_vaquera_state_toml(&temp, "[[repos]]
+path = \"repo_a\"
+tags = []
+
+[[repos]]
+path = \"repo_b\"
+tags = []
+");
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec![
+			"exec", "--oneline", "--summary", "--log-dir", "logs", "--report", "json", "--",
+			"echo out; if test -e fail; then echo 'no luck' >&2; exit 3; fi",
+		])
+		.assert()
+		.failure()
+		.stdout("repo_a\tout\nrepo_b\tout no luck\n")
+		.stderr(predicate::str::is_match(r"REPO    EXIT       DURATION  ERROR\nrepo_a  0 +[0-9.]+s\nrepo_b  3 +[0-9.]+s  no luck\n").expect("valid regex"))
+		.stderr(predicate::str::contains("1 commands exited with non-zero status code"));
+
+	assert_eq!("out\n", fs::read_to_string(temp.path().join("logs/repo_a.stdout.log")).expect("stdout log written"));
+	assert_eq!("no luck\n", fs::read_to_string(temp.path().join("logs/repo_b.stderr.log")).expect("stderr log written"));
+
+	let json = fs::read_to_string(temp.path().join("vaquera-exec.json")).expect("json report written");
+	assert!(json.contains("\"path\": \"repo_b\""));
+	assert!(json.contains("\"status\": \"failed\""));
+	assert!(json.contains("\"exit_code\": 3"));
+	assert!(json.contains("\"first_error\": \"no luck\""));
+
+	vaquera_executable()
+		.current_dir(&temp)
+		.args(vec!["exec", "--oneline", "--report", "junit", "--report-file", "junit.xml", "--", "test ! -e fail"])
+		.assert()
+		.failure();
+
+	let junit = fs::read_to_string(temp.path().join("junit.xml")).expect("junit report written");
+	assert!(junit.contains("<testsuite name=\"vaquera exec\" tests=\"2\" failures=\"1\""));
+	assert!(junit.contains("<testcase classname=\"vaquera.exec\" name=\"repo_b\""));
+	assert!(junit.contains("<failure message=\"\" type=\"exit code 1\"/>"));
+}